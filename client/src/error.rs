@@ -0,0 +1,85 @@
+use std::{fmt, io, path::PathBuf};
+
+use utils::communication::{Ping, PROTOCOL_VERSION};
+
+#[derive(Debug)]
+pub enum Error {
+    /// There is no socket at this path, so the daemon is most likely not running
+    NotRunning(PathBuf),
+    /// The socket exists, but connecting to it failed
+    Connect(io::Error),
+    /// Sending a request or receiving an answer failed
+    Communication(String),
+    /// The daemon does not answer pings, so it is older than the pings themselves
+    DaemonTooOld,
+    /// The daemon speaks another protocol version. This is what it answered the ping with
+    VersionMismatch(Ping),
+    /// The daemon answered the request with an error
+    Daemon(String),
+    /// The daemon answered with something that makes no sense for the request
+    UnexpectedAnswer,
+    /// Reading, decoding or resizing the image failed
+    Image(String),
+    /// The image is animated, which `Client::set_image` cannot display. Use `swww img` instead
+    Animated(PathBuf),
+    NoSuchOutput(String),
+    /// None of the requested outputs exist
+    NoValidOutputs,
+    /// The daemon agreed to exit, but its socket was still there after a while
+    SocketNotDeleted(PathBuf),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotRunning(path) => write!(
+                f,
+                "Socket file {} not found. Are you sure swww-daemon is running?",
+                path.display()
+            ),
+            Self::Connect(e) => write!(f, "Failed to connect to socket: {e}"),
+            Self::Communication(e) | Self::Daemon(e) | Self::Image(e) => write!(f, "{e}"),
+            Self::DaemonTooOld => write!(
+                f,
+                "swww-daemon did not tell its version, so it must be older than this swww ({}). \
+                 Restart it so that both are the same version",
+                env!("CARGO_PKG_VERSION")
+            ),
+            Self::VersionMismatch(ping) => write!(
+                f,
+                "swww-daemon {} speaks protocol version {}, but this swww ({}) speaks version \
+                 {PROTOCOL_VERSION}. Restart the daemon so that both are the same version",
+                ping.version,
+                ping.protocol,
+                env!("CARGO_PKG_VERSION")
+            ),
+            Self::UnexpectedAnswer => write!(f, "swww-daemon sent an unexpected answer"),
+            Self::Animated(path) => write!(
+                f,
+                "{} is animated, which is only supported by swww img",
+                path.display()
+            ),
+            Self::NoSuchOutput(output) => write!(f, "output {output} does not exist"),
+            Self::NoValidOutputs => write!(f, "none of the requested outputs are valid"),
+            Self::SocketNotDeleted(path) => {
+                write!(f, "Could not confirm socket deletion at: {path:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Connect(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Lets code that reports errors as strings, like the rest of swww, use `?`
+impl From<Error> for String {
+    fn from(error: Error) -> Self {
+        error.to_string()
+    }
+}
@@ -0,0 +1,255 @@
+//! Turns images into what `Request::Img` sends: one resized image for every group of outputs that
+//! would end up with the same one.
+
+use image::{
+    codecs::{png::PngDecoder, webp::WebPDecoder},
+    ImageFormat, RgbaImage,
+};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use utils::{
+    communication::{BgImg, BgInfo, Img, Resize, Span, Transition},
+    resize::span,
+};
+
+use crate::Error;
+
+/// How `Client::set_image` displays the image
+#[derive(Clone, Default)]
+pub struct ImageOptions {
+    /// Where to display the image. If empty, at every output
+    pub outputs: Vec<String>,
+    pub resize: Resize,
+    pub transition: Transition,
+}
+
+/// Decodes the image at `path`, guessing its format from its contents. Animations are refused
+/// with `Error::Animated`, rather than only showing their first frame
+pub fn decode(path: &Path) -> Result<RgbaImage, Error> {
+    let reader = match image::io::Reader::open(path) {
+        Ok(reader) => reader,
+        Err(e) => return Err(Error::Image(format!("failed to open image: {e}"))),
+    };
+    let reader = match reader.with_guessed_format() {
+        Ok(reader) => reader,
+        Err(e) => {
+            return Err(Error::Image(format!(
+                "failed to detect the image's format: {e}"
+            )))
+        }
+    };
+    if let Some(format) = reader.format() {
+        if is_animated(path, format)? {
+            return Err(Error::Animated(path.to_path_buf()));
+        }
+    }
+    match reader.decode() {
+        Ok(img) => Ok(img.into_rgba8()),
+        Err(e) => Err(Error::Image(format!("failed to decode image: {e}"))),
+    }
+}
+
+/// Gifs always count as animated, like they do for `swww img`
+fn is_animated(path: &Path, format: ImageFormat) -> Result<bool, Error> {
+    let reader = match File::open(path) {
+        Ok(file) => BufReader::new(file),
+        Err(e) => return Err(Error::Image(format!("failed to open image: {e}"))),
+    };
+    match format {
+        ImageFormat::Gif => Ok(true),
+        ImageFormat::Png => match PngDecoder::new(reader) {
+            Ok(png) => Ok(png.is_apng()),
+            Err(e) => Err(Error::Image(format!("failed to decode png: {e}"))),
+        },
+        ImageFormat::WebP => match WebPDecoder::new(reader) {
+            Ok(webp) => Ok(webp.has_animation()),
+            Err(e) => Err(Error::Image(format!("failed to decode webp: {e}"))),
+        },
+        _ => Ok(false),
+    }
+}
+
+/// Groups the requested outputs (all of them, if `requested_outputs` is empty) by the size of
+/// their buffers and by what they are displaying, since those would get the exact same image.
+/// Returns the size of each group, along with its outputs
+#[allow(clippy::type_complexity)]
+pub fn dimensions_and_outputs(
+    infos: &[BgInfo],
+    requested_outputs: &[String],
+) -> Result<(Vec<(u32, u32)>, Vec<Vec<String>>), Error> {
+    let mut outputs: Vec<Vec<String>> = Vec::new();
+    let mut dims: Vec<(u32, u32)> = Vec::new();
+    let mut imgs: Vec<&BgImg> = Vec::new();
+
+    for info in infos {
+        if !requested_outputs.is_empty() && !requested_outputs.contains(&info.name) {
+            continue;
+        }
+        let mut should_add = true;
+        let real_dim = info.real_dim();
+        for (i, (dim, img)) in dims.iter().zip(&imgs).enumerate() {
+            if real_dim == *dim && info.img == **img {
+                outputs[i].push(info.name.clone());
+                should_add = false;
+                break;
+            }
+        }
+
+        if should_add {
+            outputs.push(vec![info.name.clone()]);
+            dims.push(real_dim);
+            imgs.push(&info.img);
+        }
+    }
+    if outputs.is_empty() {
+        Err(Error::NoValidOutputs)
+    } else {
+        Ok((dims, outputs))
+    }
+}
+
+/// Resizes the image for every group of outputs. See `dimensions_and_outputs`
+pub fn img_request(
+    path: &Path,
+    img: &RgbaImage,
+    resize: &Resize,
+    infos: &[BgInfo],
+    requested_outputs: &[String],
+) -> Result<Vec<(Img, Vec<String>)>, Error> {
+    let (dims, outputs) = dimensions_and_outputs(infos, requested_outputs)?;
+    let path = canonicalize_img_path(path)?;
+    dims.into_iter()
+        .zip(outputs)
+        .map(|(dim, outputs)| {
+            let img = utils::resize::resize(img.clone(), dim, resize).map_err(Error::Image)?;
+            Ok((
+                Img {
+                    img,
+                    path: path.clone(),
+                    span: None,
+                },
+                outputs,
+            ))
+        })
+        .collect()
+}
+
+/// Cuts the image into one part for each requested output. See `utils::resize::span`
+pub fn span_request(
+    path: &Path,
+    img: RgbaImage,
+    resize: &Resize,
+    infos: &[BgInfo],
+    requested_outputs: &[String],
+) -> Result<Vec<(Img, Vec<String>)>, Error> {
+    let outputs: Vec<&BgInfo> = infos
+        .iter()
+        .filter(|info| requested_outputs.contains(&info.name))
+        .collect();
+    if outputs.is_empty() {
+        return Err(Error::NoValidOutputs);
+    }
+
+    let imgs = span(img, &outputs, resize).map_err(Error::Image)?;
+    // images from stdin can't be spanned again by the daemon, since it has no way of reading them
+    let span = match path.to_str() {
+        Some("-") => None,
+        _ => Some(Span {
+            outputs: outputs.iter().map(|info| info.name.clone()).collect(),
+            resize: resize.clone(),
+        }),
+    };
+    let path = canonicalize_img_path(path)?;
+
+    Ok(imgs
+        .into_iter()
+        .zip(outputs)
+        .map(|(img, info)| {
+            (
+                Img {
+                    img,
+                    path: path.clone(),
+                    span: span.clone(),
+                },
+                vec![info.name.clone()],
+            )
+        })
+        .collect())
+}
+
+/// The daemon runs in another working directory, so it must be sent absolute paths. `-` stands
+/// for stdin
+pub fn canonicalize_img_path(path: &Path) -> Result<PathBuf, Error> {
+    match path.canonicalize() {
+        Ok(p) => Ok(p),
+        Err(e) => {
+            if let Some("-") = path.to_str() {
+                Ok(PathBuf::from("STDIN"))
+            } else {
+                Err(Error::Image(format!(
+                    "failed no canonicalize image path: {e}"
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(name: &str, dim: (u32, u32), scale_factor: i32) -> BgInfo {
+        BgInfo {
+            name: name.to_string(),
+            dim,
+            position: (0, 0),
+            scale_factor,
+            img: BgImg::Color([0, 0, 0]),
+            animating: false,
+            transitioning: false,
+        }
+    }
+
+    #[test]
+    fn outputs_of_the_same_size_should_share_an_image() {
+        let infos = [
+            info("DP-1", (1920, 1080), 1),
+            info("DP-2", (960, 540), 2),
+            info("HDMI-A-1", (1280, 720), 1),
+        ];
+        let (dims, outputs) = dimensions_and_outputs(&infos, &[]).unwrap();
+        assert_eq!(dims, vec![(1920, 1080), (1280, 720)]);
+        assert_eq!(
+            outputs,
+            vec![vec!["DP-1", "DP-2"], vec!["HDMI-A-1"]]
+                .into_iter()
+                .map(|o| o.into_iter().map(String::from).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        );
+
+        let (dims, _) = dimensions_and_outputs(&infos, &["HDMI-A-1".to_string()]).unwrap();
+        assert_eq!(dims, vec![(1280, 720)]);
+        assert!(matches!(
+            dimensions_and_outputs(&infos, &["eDP-1".to_string()]),
+            Err(Error::NoValidOutputs)
+        ));
+    }
+
+    #[test]
+    fn animated_images_should_be_refused() {
+        let dir = std::env::temp_dir();
+        let still = dir.join(format!("swww-client-still-{}.png", std::process::id()));
+        let gif = dir.join(format!("swww-client-animated-{}.gif", std::process::id()));
+        RgbaImage::new(1, 1).save(&still).unwrap();
+        RgbaImage::new(1, 1).save(&gif).unwrap();
+
+        assert!(decode(&still).is_ok());
+        assert!(matches!(decode(&gif), Err(Error::Animated(path)) if path == gif));
+        std::fs::remove_file(still).unwrap();
+        std::fs::remove_file(gif).unwrap();
+    }
+}
@@ -0,0 +1,284 @@
+//! Controls `swww-daemon` from Rust, without going through the `swww` binary.
+//!
+//! A `Client` talks to the daemon of the current namespace: the one in `SWWW_NAMESPACE`, or the
+//! one named after the Wayland display we are running in. Daemons only understand clients that
+//! speak their protocol version, so call `Client::check_version` once before anything else:
+//!
+//! ```no_run
+//! use swww_client::{Client, ImageOptions, TransitionType};
+//!
+//! let client = Client::new();
+//! client.check_version()?;
+//!
+//! let mut opts = ImageOptions::default();
+//! opts.outputs = vec!["DP-1".to_string()];
+//! opts.transition.transition_type = TransitionType::Fade;
+//! client.set_image("wallpaper.png", &opts)?;
+//!
+//! for info in client.query()? {
+//!     println!("{info}");
+//! }
+//! # Ok::<(), swww_client::Error>(())
+//! ```
+
+use std::{
+    io::ErrorKind,
+    net::Shutdown,
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use utils::communication::{get_legacy_socket_path, get_socket_path, Answer, Request};
+
+/// Everything sent through the socket, for making requests with `Client::request`
+pub use utils::communication;
+pub use utils::communication::{
+    BgImg, BgInfo, Coord, Filter, Gradient, GradientShape, Ping, Position, Resize, ResizeStrategy,
+    Transition, TransitionType, PROTOCOL_VERSION,
+};
+
+mod error;
+pub mod img;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+pub use error::Error;
+pub use img::ImageOptions;
+
+pub struct Client {
+    socket_path: PathBuf,
+}
+
+impl Client {
+    /// Talks to the daemon of the current namespace. See `utils::communication::get_namespace`
+    ///
+    /// In the default namespace, if no daemon listens at the namespace's socket but one does at
+    /// the socket of swww 0.7.3 and older, talks to that one instead. That way a daemon left
+    /// running through an upgrade can still be found, told apart with `check_version` and killed
+    #[must_use]
+    pub fn new() -> Self {
+        let socket_path = get_socket_path();
+        if !socket_path.exists() {
+            if let Some(legacy) = get_legacy_socket_path().filter(|path| path.exists()) {
+                return Self {
+                    socket_path: legacy,
+                };
+            }
+        }
+        Self { socket_path }
+    }
+
+    /// Talks to the daemon listening at `socket_path`, whatever its namespace
+    #[must_use]
+    pub fn at(socket_path: PathBuf) -> Self {
+        Self { socket_path }
+    }
+
+    #[must_use]
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Connects to the daemon's socket, trying 5 times, 100 milliseconds apart, in case the daemon
+    /// was just spawned. The stream is always in blocking mode
+    pub fn connect(&self) -> Result<UnixStream, Error> {
+        let mut error = None;
+        for _ in 0..5 {
+            match UnixStream::connect(&self.socket_path) {
+                Ok(socket) => {
+                    if let Err(e) = socket.set_nonblocking(false) {
+                        return Err(Error::Communication(format!(
+                            "Failed to set blocking connection: {e}"
+                        )));
+                    }
+                    return Ok(socket);
+                }
+                Err(e) => error = Some(e),
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        match error.unwrap() {
+            e if e.kind() == ErrorKind::NotFound => {
+                Err(Error::NotRunning(self.socket_path.clone()))
+            }
+            e => Err(Error::Connect(e)),
+        }
+    }
+
+    /// Whether a daemon answers at the socket. Unlike `connect`, this only tries once
+    pub fn is_running(&self) -> Result<bool, Error> {
+        let socket = match UnixStream::connect(&self.socket_path) {
+            Ok(socket) => socket,
+            // a socket that refuses connections was left behind by a daemon that is gone
+            Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused) => {
+                return Ok(false)
+            }
+            Err(e) => return Err(Error::Connect(e)),
+        };
+        ping(socket).map(|_| true)
+    }
+
+    /// Asks the daemon for its version. This works whatever version the daemon is, as long as it
+    /// is recent enough to know pings at all
+    pub fn ping(&self) -> Result<Ping, Error> {
+        ping(self.connect()?)
+    }
+
+    /// Makes sure the daemon understands our requests, and that we understand its answers
+    pub fn check_version(&self) -> Result<Ping, Error> {
+        let ping = self.ping()?;
+        if ping.protocol == PROTOCOL_VERSION {
+            Ok(ping)
+        } else {
+            Err(Error::VersionMismatch(ping))
+        }
+    }
+
+    /// Sends any request, returning the daemon's answer. `Answer::Err` is turned into
+    /// `Error::Daemon`.
+    ///
+    /// Requests that keep the connection open afterwards, like `Request::Subscribe`, must be made
+    /// through `connect` instead
+    pub fn request(&self, request: &Request) -> Result<Answer, Error> {
+        let socket = self.connect()?;
+        request.send(&socket).map_err(Error::Communication)?;
+        let answer = match request {
+            Request::Wait(_) => Answer::receive_eventually(socket),
+            _ => Answer::receive(socket),
+        };
+        match answer.map_err(Error::Communication)? {
+            Answer::Err(e) => Err(Error::Daemon(e)),
+            answer => {
+                request.cache_accepted();
+                Ok(answer)
+            }
+        }
+    }
+
+    pub fn query(&self) -> Result<Vec<BgInfo>, Error> {
+        match self.request(&Request::Query)? {
+            Answer::Info(infos) => Ok(infos),
+            _ => Err(Error::UnexpectedAnswer),
+        }
+    }
+
+    /// Displays the image at `path`, resized for every output. Animated images are refused with
+    /// `Error::Animated`
+    pub fn set_image(&self, path: impl AsRef<Path>, opts: &ImageOptions) -> Result<(), Error> {
+        let path = path.as_ref();
+        let img = img::decode(path)?;
+        let infos = self.query()?;
+        if let Some(output) = opts
+            .outputs
+            .iter()
+            .find(|output| !infos.iter().any(|info| &info.name == *output))
+        {
+            return Err(Error::NoSuchOutput(output.clone()));
+        }
+
+        let imgs = if let ResizeStrategy::Span { .. } = opts.resize.strategy {
+            let outputs: Vec<String> = if opts.outputs.is_empty() {
+                infos.iter().map(|info| info.name.clone()).collect()
+            } else {
+                opts.outputs.clone()
+            };
+            img::span_request(path, img, &opts.resize, &infos, &outputs)?
+        } else {
+            img::img_request(path, &img, &opts.resize, &infos, &opts.outputs)?
+        };
+        self.request_ok(&Request::Img(vec![(opts.transition.clone(), imgs)]))
+    }
+
+    /// Fills the outputs with `color`. If `outputs` is empty, all of them
+    pub fn clear(&self, color: [u8; 3], outputs: &[String]) -> Result<(), Error> {
+        self.request_ok(&Request::Clear(communication::Clear {
+            color,
+            gradient: None,
+            outputs: outputs.to_vec(),
+            transition: Transition::default(),
+        }))
+    }
+
+    /// Blocks until none of `outputs` is transitioning. If `outputs` is empty, until no output is
+    pub fn wait(&self, outputs: &[String]) -> Result<(), Error> {
+        self.request_ok(&Request::Wait(outputs.to_vec()))
+    }
+
+    /// Makes the daemon exit, returning once it deleted its socket. `Request::Kill` and
+    /// `Answer::Ok` never moved on the wire, so this also works with daemons speaking other
+    /// protocol versions, down to those from before pings existed
+    pub fn kill(&self) -> Result<(), Error> {
+        self.request_ok(&Request::Kill)?;
+        #[cfg(debug_assertions)]
+        let tries = 20;
+        #[cfg(not(debug_assertions))]
+        let tries = 10;
+        for _ in 0..tries {
+            if !self.socket_path.exists() {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        Err(Error::SocketNotDeleted(self.socket_path.clone()))
+    }
+
+    fn request_ok(&self, request: &Request) -> Result<(), Error> {
+        match self.request(request)? {
+            Answer::Ok => Ok(()),
+            _ => Err(Error::UnexpectedAnswer),
+        }
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Asks the daemon at the other end of `socket` for its version
+fn ping(socket: UnixStream) -> Result<Ping, Error> {
+    Request::Ping.send(&socket).map_err(Error::Communication)?;
+    // in case the daemon takes this for something with more to it, and keeps waiting for the rest
+    if let Err(e) = socket.shutdown(Shutdown::Write) {
+        return Err(Error::Communication(format!(
+            "failed to shutdown socket: {e}"
+        )));
+    }
+    match Answer::receive_ping(socket).map_err(Error::Communication)? {
+        Some(ping) => Ok(ping),
+        None => Err(Error::DaemonTooOld),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::fake_daemon;
+
+    #[test]
+    fn should_reject_other_protocol_versions() {
+        let client = fake_daemon("client-version", || {
+            Answer::Ping(Ping {
+                protocol: PROTOCOL_VERSION + 1,
+                version: "99.0.0".to_string(),
+                capabilities: Vec::new(),
+            })
+        });
+        match client.check_version() {
+            Err(Error::VersionMismatch(ping)) => assert_eq!(ping.version, "99.0.0"),
+            _ => panic!("accepted another protocol version"),
+        }
+        std::fs::remove_file(client.socket_path()).unwrap();
+    }
+
+    #[test]
+    fn daemon_errors_should_be_typed() {
+        let client = fake_daemon("client-error", || Answer::Err("no".to_string()));
+        assert!(matches!(
+            client.clear([0, 0, 0], &[]),
+            Err(Error::Daemon(e)) if e == "no"
+        ));
+        std::fs::remove_file(client.socket_path()).unwrap();
+    }
+}
@@ -0,0 +1,29 @@
+//! Helpers for testing code built on top of this crate, without a real daemon. Enabled with the
+//! `test-util` cargo feature.
+
+use std::{io::Read, os::unix::net::UnixListener, thread};
+
+use utils::communication::{Answer, Request};
+
+use crate::Client;
+
+/// Listens at a socket of its own, answering every request with `answer`, like a daemon would.
+/// `name` must be unique among the tests running at the same time.
+///
+/// Returns a client talking to it. Remove its socket once done with it
+pub fn fake_daemon(name: &str, answer: impl Fn() -> Answer + Send + 'static) -> Client {
+    let socket_path =
+        std::env::temp_dir().join(format!("swww-test-{name}-{}.socket", std::process::id()));
+    let _ = std::fs::remove_file(&socket_path);
+    let listener = UnixListener::bind(&socket_path).unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            Request::receive(&stream).unwrap();
+            answer().send(&stream).unwrap();
+            // wait for the client to hang up
+            let _ = stream.read(&mut [0]);
+        }
+    });
+    Client::at(socket_path)
+}
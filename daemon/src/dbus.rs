@@ -0,0 +1,274 @@
+//! The optional `org.swww.Daemon` D-Bus interface, enabled with the `dbus` cargo feature.
+//!
+//! Its methods are made through the daemon's own socket, exactly like `swww` would make them, so
+//! they go through the same validation and handling as everything else. Wallpaper changes come from
+//! a subscription to our `Events`, and are forwarded as the `WallpaperChanged` signal.
+//!
+//! Only one daemon can own the name in a session bus. Daemons in other namespaces go on without it.
+
+use log::{debug, error, info};
+
+use std::{collections::HashMap, io::BufReader, os::unix::net::UnixStream, sync::Arc, thread};
+
+use swww_client::{BgImg, BgInfo, Client, ImageOptions};
+use utils::communication::{Event, Field};
+use zbus::{
+    blocking::{Connection, ConnectionBuilder},
+    dbus_interface, fdo,
+    zvariant::{OwnedValue, Value},
+    SignalContext,
+};
+
+use crate::{events::Events, processor::TSTACK_SIZE};
+
+pub const NAME: &str = "org.swww.Daemon";
+pub const PATH: &str = "/org/swww/Daemon";
+
+/// An `a{sv}` dictionary. `Query` answers with one for every output, with the same fields as
+/// `swww query --json`, leaving out the null ones
+type Dict = HashMap<String, OwnedValue>;
+
+struct Daemon {
+    client: Arc<Client>,
+}
+
+#[dbus_interface(name = "org.swww.Daemon")]
+impl Daemon {
+    /// Displays the image at `path` with `swww img`'s default options. If `outputs` is empty, at
+    /// every output
+    async fn set_image(&self, path: String, outputs: Vec<String>) -> fdo::Result<()> {
+        let client = Arc::clone(&self.client);
+        let opts = ImageOptions {
+            outputs,
+            ..ImageOptions::default()
+        };
+        // decoding and resizing takes a while, and would hold up every other call
+        blocking::unblock(move || client.set_image(path, &opts))
+            .await
+            .map_err(failed)
+    }
+
+    async fn clear(&self, color: (u8, u8, u8), outputs: Vec<String>) -> fdo::Result<()> {
+        let client = Arc::clone(&self.client);
+        blocking::unblock(move || client.clear([color.0, color.1, color.2], &outputs))
+            .await
+            .map_err(failed)
+    }
+
+    async fn query(&self) -> fdo::Result<Vec<Dict>> {
+        let client = Arc::clone(&self.client);
+        let infos = blocking::unblock(move || client.query())
+            .await
+            .map_err(failed)?;
+        Ok(infos.iter().map(output).collect())
+    }
+
+    #[dbus_interface(signal)]
+    async fn wallpaper_changed(
+        ctxt: &SignalContext<'_>,
+        output: &str,
+        wallpaper: Dict,
+    ) -> zbus::Result<()>;
+}
+
+/// Registers the interface in the session bus. Failing to do so is not fatal, since swww works just
+/// as well without it
+pub fn start(events: &Events) {
+    let builder = match ConnectionBuilder::session() {
+        Ok(builder) => builder,
+        Err(e) => {
+            error!("failed to connect to the session bus: {e}");
+            return;
+        }
+    };
+    match serve(builder, Client::new(), events) {
+        Ok(()) => info!("Registered {NAME} in the session bus"),
+        Err(e) => error!("failed to register {NAME} in the session bus: {e}"),
+    }
+}
+
+fn serve(builder: ConnectionBuilder, client: Client, events: &Events) -> Result<(), String> {
+    let connection = builder
+        .name(NAME)
+        .and_then(|builder| {
+            builder.serve_at(
+                PATH,
+                Daemon {
+                    client: Arc::new(client),
+                },
+            )
+        })
+        .and_then(ConnectionBuilder::build)
+        .map_err(|e| e.to_string())?;
+
+    let (subscriber, stream) = match UnixStream::pair() {
+        Ok(pair) => pair,
+        Err(e) => return Err(format!("failed to create the events' stream: {e}")),
+    };
+    events.subscribe(subscriber)?;
+    // the thread also keeps the connection, and with it the interface, alive
+    match thread::Builder::new()
+        .name("dbus".to_string()) //Name our threads  for better log messages
+        .stack_size(TSTACK_SIZE) //the default of 2MB is way too overkill for this
+        .spawn(move || forward_events(&connection, stream))
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("failed to spawn 'dbus' thread: {e}")),
+    }
+}
+
+/// Emits the signals for the events the interface has them for
+fn forward_events(connection: &Connection, stream: UnixStream) {
+    let mut reader = BufReader::new(stream);
+    loop {
+        let event = match Event::receive(&mut reader) {
+            Ok(event) => event,
+            Err(e) => {
+                error!("stopped forwarding events to D-Bus: {e}");
+                return;
+            }
+        };
+        if let Event::WallpaperChanged { output, img } = event {
+            let body = (output, wallpaper(&img));
+            if let Err(e) =
+                connection.emit_signal(None::<&str>, PATH, NAME, "WallpaperChanged", &body)
+            {
+                debug!("failed to emit WallpaperChanged: {e}");
+            }
+        }
+    }
+}
+
+fn output(info: &BgInfo) -> Dict {
+    info.fields()
+        .into_iter()
+        .map(|(name, field)| (name.to_string(), value(field)))
+        .collect()
+}
+
+/// What is being displayed: either the `image`'s path, the `color`, or the `gradient`'s colors.
+/// Colors are given as rrggbb
+fn wallpaper(img: &BgImg) -> Dict {
+    let (name, field) = img.field();
+    HashMap::from([(name.to_string(), value(field))])
+}
+
+fn value(field: Field) -> OwnedValue {
+    match field {
+        Field::Text(text) => Value::from(text),
+        Field::Unsigned(n) => Value::from(n),
+        Field::Signed(n) => Value::from(n),
+        Field::Bool(b) => Value::from(b),
+        Field::Colors(colors) => Value::from(colors),
+    }
+    .into()
+}
+
+fn failed(e: swww_client::Error) -> fdo::Error {
+    fdo::Error::Failed(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    //! These run against a private bus, from `dbus-daemon --session`. They are skipped when it
+    //! isn't installed
+    use super::*;
+    use std::{
+        io::BufRead,
+        path::PathBuf,
+        process::{Child, Command, Stdio},
+    };
+    use swww_client::test_util::fake_daemon;
+    use utils::communication::Answer;
+    use zbus::blocking::Proxy;
+
+    struct Bus {
+        process: Child,
+        address: String,
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.process.kill();
+            let _ = self.process.wait();
+        }
+    }
+
+    fn private_bus() -> Option<Bus> {
+        let mut process = match Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(process) => process,
+            Err(e) => {
+                eprintln!("skipping, failed to spawn dbus-daemon: {e}");
+                return None;
+            }
+        };
+        let mut address = String::new();
+        let mut stdout = BufReader::new(process.stdout.take().unwrap());
+        stdout.read_line(&mut address).unwrap();
+        Some(Bus {
+            process,
+            address: address.trim().to_string(),
+        })
+    }
+
+    #[test]
+    fn should_answer_queries_and_signal_wallpaper_changes() {
+        let bus = match private_bus() {
+            Some(bus) => bus,
+            None => return,
+        };
+        let client = fake_daemon("dbus", || {
+            Answer::Info(vec![BgInfo {
+                name: "DP-1".to_string(),
+                dim: (1920, 1080),
+                position: (0, 0),
+                scale_factor: 1,
+                img: BgImg::Img(PathBuf::from("/a.png")),
+                animating: false,
+                transitioning: false,
+            }])
+        });
+        let events = Events::new();
+        let socket_path = client.socket_path().to_path_buf();
+        serve(
+            ConnectionBuilder::address(bus.address.as_str()).unwrap(),
+            client,
+            &events,
+        )
+        .unwrap();
+
+        let connection = ConnectionBuilder::address(bus.address.as_str())
+            .unwrap()
+            .build()
+            .unwrap();
+        let proxy = Proxy::new(&connection, NAME, PATH, NAME).unwrap();
+        let outputs: Vec<Dict> = proxy.call("Query", &()).unwrap();
+        assert_eq!(outputs.len(), 1);
+        let field = |name: &str| Value::from(outputs[0][name].clone());
+        assert_eq!(field("name"), Value::from("DP-1"));
+        assert_eq!(field("width"), Value::from(1920u32));
+        assert_eq!(field("scale"), Value::from(1));
+        assert_eq!(field("image"), Value::from("/a.png"));
+        assert!(!outputs[0].contains_key("color"));
+
+        let mut signals = proxy.receive_signal("WallpaperChanged").unwrap();
+        events.send(Event::WallpaperChanged {
+            output: "DP-1".to_string(),
+            img: BgImg::Color([255, 0, 0]),
+        });
+        let signal = signals.next().unwrap();
+        let (output, wallpaper): (String, Dict) = signal.body().unwrap();
+        assert_eq!(output, "DP-1");
+        assert_eq!(
+            Value::from(wallpaper["color"].clone()),
+            Value::from("ff0000")
+        );
+        assert!(!wallpaper.contains_key("image"));
+
+        std::fs::remove_file(socket_path).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+//! Sends the daemon's `Event`s to every client subscribed with `Request::Subscribe`.
+//!
+//! Every subscriber has its own queue of events, written from a thread of its own, so that slow
+//! subscribers never hold up the daemon or each other. Subscribers that take too long to read them,
+//! or that closed the connection, are dropped.
+
+use log::{debug, error};
+
+use std::{
+    io::BufWriter,
+    os::unix::net::UnixStream,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+use utils::communication::Event;
+
+use crate::processor::TSTACK_SIZE;
+
+/// How long a subscriber may take to read an event before it is dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+enum Msg {
+    Subscribe(UnixStream),
+    Event(Event),
+}
+
+#[derive(Clone)]
+pub struct Events {
+    sender: mpsc::Sender<Msg>,
+}
+
+impl Events {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        if let Err(e) = thread::Builder::new()
+            .name("events".to_string()) //Name our threads  for better log messages
+            .stack_size(TSTACK_SIZE) //the default of 2MB is way too overkill for this
+            .spawn(move || broadcast(receiver))
+        {
+            error!("failed to spawn 'events' thread: {e}");
+        }
+        Self { sender }
+    }
+
+    pub fn subscribe(&self, stream: UnixStream) -> Result<(), String> {
+        if let Err(e) = stream.set_write_timeout(Some(WRITE_TIMEOUT)) {
+            return Err(format!("failed to set write timeout: {e}"));
+        }
+        match self.sender.send(Msg::Subscribe(stream)) {
+            Ok(()) => Ok(()),
+            Err(_) => Err("the events thread is not running".to_string()),
+        }
+    }
+
+    pub fn send(&self, event: Event) {
+        // if the thread failed to spawn, there is nobody to tell
+        let _ = self.sender.send(Msg::Event(event));
+    }
+}
+
+fn broadcast(receiver: Receiver<Msg>) {
+    let mut subscribers: Vec<Sender<Event>> = Vec::new();
+    for msg in receiver {
+        match msg {
+            Msg::Subscribe(stream) => {
+                let (sender, queue) = mpsc::channel();
+                match thread::Builder::new()
+                    .name("subscriber".to_string()) //Name our threads  for better log messages
+                    .stack_size(TSTACK_SIZE) //the default of 2MB is way too overkill for this
+                    .spawn(move || write_events(&stream, queue))
+                {
+                    Ok(_) => subscribers.push(sender),
+                    Err(e) => error!("failed to spawn 'subscriber' thread: {e}"),
+                }
+            }
+            // the queues of dropped subscribers are closed
+            Msg::Event(event) => subscribers.retain(|sender| sender.send(event.clone()).is_ok()),
+        }
+    }
+}
+
+fn write_events(stream: &UnixStream, queue: Receiver<Event>) {
+    for event in queue {
+        if let Err(e) = event.send(BufWriter::new(stream)) {
+            debug!("dropping subscriber: {e}");
+            return;
+        }
+    }
+}
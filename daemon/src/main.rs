@@ -4,7 +4,7 @@ use simplelog::{ColorChoice, LevelFilter, TermLogger, TerminalMode, ThreadLogMod
 use smithay_client_toolkit::{
     environment::Environment,
     get_surface_scale_factor,
-    output::{with_output_info, OutputInfo},
+    output::{add_output_listener, with_output_info, OutputInfo, OutputListener},
     reexports::{
         calloop::{
             self,
@@ -26,18 +26,39 @@ use std::{
     cell::{Cell, RefCell, RefMut},
     fs,
     os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
     rc::Rc,
+    sync::{Arc, Mutex},
 };
 
 use utils::{
-    communication::{get_socket_path, Answer, BgImg, BgInfo, Clear, Img, Request},
+    communication::{
+        get_cache_path, get_socket_path, set_namespace, Answer, BgImg, BgInfo, Clear, Event,
+        ImageRequest, Img, Ping, Request, Restore, NAMESPACE_ENV, PROTOCOL_VERSION,
+    },
     comp_decomp::ReadiedPack,
+    config::Config,
+    gradient,
 };
 
+#[cfg(feature = "dbus")]
+mod dbus;
+mod events;
 mod processor;
 mod wayland;
 
-use processor::{ImgWithDim, Processor};
+/// Optional features this daemon was built with, sent in answer to `Request::Ping`
+const CAPABILITIES: &[&str] = &[
+    #[cfg(feature = "dbus")]
+    "dbus",
+];
+
+const USAGE: &str = "Usage: swww-daemon [--namespace <NAMESPACE>]
+
+The daemon should be started through `swww init`. See swww-daemon(1)";
+
+use events::Events;
+use processor::{ImgWithDim, ProcessedImg, Processor};
 
 #[derive(PartialEq, Copy, Clone)]
 enum RenderEvent {
@@ -50,13 +71,16 @@ struct Bg {
     surface: wl_surface::WlSurface,
     layer_surface: Main<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1>,
     next_render_event: Rc<Cell<Option<RenderEvent>>>,
+    /// Tells us when the output is moved. Dropping it stops the updates
+    _output_listener: OutputListener,
+    next_position: Arc<Mutex<Option<(i32, i32)>>>,
     pool: MemPool,
 }
 
 impl Bg {
     fn new(
         output: &wl_output::WlOutput,
-        output_name: String,
+        output_info: &OutputInfo,
         surface: wl_surface::WlSurface,
         layer_shell: &Attached<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
         pool: MemPool,
@@ -93,6 +117,14 @@ impl Bg {
             }
         });
 
+        let next_position = Arc::new(Mutex::new(None::<(i32, i32)>));
+        let next_position_handle = Arc::clone(&next_position);
+        let output_listener = add_output_listener(output, move |_, info, _| {
+            if let Ok(mut next_position) = next_position_handle.lock() {
+                *next_position = Some(info.location);
+            }
+        });
+
         // Commit so that the server will send a configure event
         surface.commit();
 
@@ -100,12 +132,17 @@ impl Bg {
             surface,
             layer_surface,
             next_render_event,
+            _output_listener: output_listener,
+            next_position,
             pool,
             info: BgInfo {
-                name: output_name,
+                name: output_info.name.clone(),
                 dim: (0, 0),
+                position: output_info.location,
                 scale_factor: 1,
                 img: BgImg::Color([0, 0, 0]),
+                animating: false,
+                transitioning: false,
             },
         }
     }
@@ -142,6 +179,32 @@ impl Bg {
         }
     }
 
+    /// Returns whether the output was moved since the last call
+    fn update_position(&mut self) -> bool {
+        let next_position = match self.next_position.lock() {
+            Ok(mut next_position) => next_position.take(),
+            Err(_) => None,
+        };
+        match next_position {
+            Some(position) if position != self.info.position => {
+                self.info.position = position;
+                debug!("Moved {}", self.info);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Must be called whenever the output starts displaying something else, so that the
+    /// subscribers are told about it
+    fn set_img(&mut self, img: BgImg, events: &Events) {
+        self.info.img = img;
+        events.send(Event::WallpaperChanged {
+            output: self.info.name.clone(),
+            img: self.info.img.clone(),
+        });
+    }
+
     ///'color' argument is in rbg. We copy it correctly to brgx inside the function
     fn clear(&mut self, color: [u8; 3]) {
         self.info.img = BgImg::Color(color);
@@ -209,6 +272,7 @@ impl Drop for Bg {
 }
 
 fn main() -> Result<(), String> {
+    parse_args()?;
     make_logger();
 
     let listener = make_socket()?;
@@ -220,11 +284,15 @@ fn main() -> Result<(), String> {
     let (env, display, queue) = wayland::make_wayland_environment();
 
     let bgs = Rc::new(RefCell::new(Vec::new()));
+    let events = Events::new();
+    #[cfg(feature = "dbus")]
+    dbus::start(&events);
 
     let layer_shell = env.require_global::<zwlr_layer_shell_v1::ZwlrLayerShellV1>();
 
     let env_handle = env.clone();
     let bgs_handle = Rc::clone(&bgs);
+    let events_handle = events.clone();
     let output_handler = move |output: wl_output::WlOutput, info: &OutputInfo| {
         create_backgrounds(
             &output,
@@ -232,6 +300,7 @@ fn main() -> Result<(), String> {
             &env_handle,
             &bgs_handle,
             &layer_shell.clone(),
+            &events_handle,
         );
     };
     // Process currently existing outputs
@@ -245,7 +314,7 @@ fn main() -> Result<(), String> {
         env.listen_for_outputs(move |output, info, _| output_handler(output, info));
 
     //NOTE: we can't move display into the function because it causes a segfault
-    main_loop(&bgs, queue, &display, listener)?;
+    main_loop(&bgs, queue, &display, listener, events)?;
     info!("Finished running event loop.");
 
     let socket_addr = get_socket_path();
@@ -260,6 +329,35 @@ fn main() -> Result<(), String> {
     Ok(())
 }
 
+/// The only option is `--namespace`, which takes priority over `SWWW_NAMESPACE`, just like in `swww`
+fn parse_args() -> Result<(), String> {
+    let mut namespace = std::env::var(NAMESPACE_ENV)
+        .ok()
+        .filter(|namespace| !namespace.is_empty());
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--namespace" => match args.next() {
+                Some(value) => namespace = Some(value),
+                None => return Err("--namespace requires a value".to_string()),
+            },
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            _ => match arg.strip_prefix("--namespace=") {
+                Some(value) => namespace = Some(value.to_string()),
+                None => return Err(format!("unexpected argument '{arg}', see --help")),
+            },
+        }
+    }
+
+    match namespace {
+        Some(namespace) => set_namespace(&namespace),
+        None => Ok(()),
+    }
+}
+
 fn make_logger() {
     let config = simplelog::ConfigBuilder::new()
         .set_thread_level(LevelFilter::Info) //let me see where the processing is happening
@@ -281,10 +379,16 @@ fn create_backgrounds(
     env: &Environment<wayland::Env>,
     bgs: &Rc<RefCell<Vec<Bg>>>,
     layer_shell: &Attached<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
+    events: &Events,
 ) {
     if info.obsolete {
         // an output has been removed, release it
-        bgs.borrow_mut().retain(|bg| bg.info.name != info.name);
+        let mut bgs = bgs.borrow_mut();
+        let len = bgs.len();
+        bgs.retain(|bg| bg.info.name != info.name);
+        if bgs.len() != len {
+            events.send(Event::OutputRemoved(info.name.clone()));
+        }
         output.release();
     } else {
         // an output has been created, construct a surface for it
@@ -308,8 +412,9 @@ fn create_backgrounds(
         empty_region.destroy();
 
         debug!("New background with output: {:?}", info);
-        let bg = Bg::new(output, info.name.clone(), surface, layer_shell, pool);
+        let bg = Bg::new(output, info, surface, layer_shell, pool);
         bgs.borrow_mut().push(bg);
+        events.send(Event::OutputAdded(info.name.clone()));
     }
 }
 
@@ -348,6 +453,34 @@ fn register_signals(handle: &LoopHandle<LoopSignal>) -> Result<(), String> {
     }
 }
 
+/// Reloads the config file on SIGHUP
+fn register_reload_signal<'a>(
+    handle: &LoopHandle<'a, LoopSignal>,
+    config: &'a RefCell<Result<Config, String>>,
+) -> Result<(), String> {
+    match signals::Signals::new(&[Signal::SIGHUP]) {
+        Ok(signals) => {
+            if let Err(e) = handle.insert_source(signals, |_, _, _| {
+                info!("Received SIGHUP. Reloading config file...");
+                *config.borrow_mut() = load_config();
+            }) {
+                Err(format!("failed to insert reload signal source: {e}"))
+            } else {
+                Ok(())
+            }
+        }
+        Err(e) => Err(format!("failed to register signal to reload config: {e}")),
+    }
+}
+
+fn load_config() -> Result<Config, String> {
+    let config = Config::load();
+    if let Err(e) = &config {
+        error!("Failed to load config file: {e}");
+    }
+    config
+}
+
 fn register_channel<'a>(
     handle: &LoopHandle<'a, LoopSignal>,
     bgs: &'a Rc<RefCell<Vec<Bg>>>,
@@ -362,11 +495,64 @@ fn register_channel<'a>(
     Ok(())
 }
 
+/// Receives the images processed by the daemon itself, in playlists and spans
+fn register_img_channel<'a>(
+    handle: &LoopHandle<'a, LoopSignal>,
+    bgs: &'a Rc<RefCell<Vec<Bg>>>,
+    processor: &'a Rc<RefCell<Processor>>,
+    img_recv: Channel<ProcessedImg>,
+) -> Result<(), String> {
+    if let Err(e) = handle.insert_source(img_recv, |evt, _, _| {
+        if let channel::Event::Msg((playlist, (transition, mut imgs))) = evt {
+            if let Some(id) = playlist {
+                // the playlist may have been stopped in some outputs while this was on its way
+                let processor = processor.borrow();
+                let outputs = processor.playlist_outputs(id);
+                for (_, img_outputs) in imgs.iter_mut() {
+                    img_outputs.retain(|output| outputs.contains(output));
+                }
+                imgs.retain(|(_, img_outputs)| !img_outputs.is_empty());
+                if imgs.is_empty() {
+                    debug!("Dropping an image from a stopped playlist");
+                    return;
+                }
+                // recorded in the history like any other image, so `swww back` goes through the
+                // playlist. This does push the older wallpapers out of the history
+                match get_cache_path() {
+                    Ok(cache_path) => Request::cache_images(&imgs, cache_path, true),
+                    Err(e) => error!("failed to get cache path: {e}"),
+                }
+            }
+            let mut bgs = bgs.borrow_mut();
+            let imgs = split_by_current_img(&bgs, imgs);
+            let old_imgs = get_old_imgs(&mut bgs, &imgs);
+            if old_imgs.len() != imgs.len() {
+                error!("Received an image for outputs that don't exist");
+            } else if let Err(e) = processor::check_sizes(&imgs, &old_imgs) {
+                error!("Failed to transition to the received image: {e}");
+            } else {
+                set_imgs(&mut bgs, &imgs, processor.borrow().events());
+                if let Answer::Err(e) =
+                    processor
+                        .borrow_mut()
+                        .transition(&transition, imgs, old_imgs)
+                {
+                    error!("Failed to transition to the received image: {e}");
+                }
+            }
+        }
+    }) {
+        return Err(format! {"failed to register image channel: {e}"});
+    }
+    Ok(())
+}
+
 fn register_socket<'a>(
     handle: &LoopHandle<'a, LoopSignal>,
     bgs: &'a Rc<RefCell<Vec<Bg>>>,
     display: &'a Display,
     processor: &'a Rc<RefCell<Processor>>,
+    config: &'a RefCell<Result<Config, String>>,
     listener: UnixListener,
 ) -> Result<(), String> {
     if let Err(e) = listener.set_nonblocking(true) {
@@ -378,7 +564,13 @@ fn register_socket<'a>(
             let mut processor = processor.borrow_mut();
             match listener.accept() {
                 Ok((stream, _)) => {
-                    match recv_socket_msg(bgs.borrow_mut(), stream, loop_signal, &mut processor) {
+                    match recv_socket_msg(
+                        bgs.borrow_mut(),
+                        stream,
+                        loop_signal,
+                        &mut processor,
+                        &config.borrow(),
+                    ) {
                         Err(e) => error!("Failed to receive socket message: {}", e),
                         Ok(()) => {
                             //We must flush here because if multiple requests are sent at once the loop
@@ -405,11 +597,18 @@ fn main_loop(
     queue: EventQueue,
     display: &Display,
     listener: UnixListener,
+    events: Events,
 ) -> Result<(), String> {
     //We use 1 because we can't send a new frame without being absolutely sure that all previous
     //have already been displayed. Using 0 causes the animation to stop.
     let (frame_sender, frame_receiver) = calloop::channel::sync_channel(1);
-    let processor = Rc::new(RefCell::new(Processor::new(frame_sender)));
+    let (img_sender, img_receiver) = calloop::channel::channel();
+    let processor = Rc::new(RefCell::new(Processor::new(
+        frame_sender,
+        img_sender,
+        events,
+    )));
+    let config = RefCell::new(load_config());
     let mut event_loop = match calloop::EventLoop::<calloop::LoopSignal>::try_new() {
         Ok(el) => el,
         Err(e) => return Err(e.to_string()),
@@ -417,8 +616,10 @@ fn main_loop(
     let event_handle = event_loop.handle();
 
     register_signals(&event_handle)?;
+    register_reload_signal(&event_handle, &config)?;
     register_channel(&event_handle, bgs, frame_receiver)?;
-    register_socket(&event_handle, bgs, display, &processor, listener)?;
+    register_img_channel(&event_handle, bgs, &processor, img_receiver)?;
+    register_socket(&event_handle, bgs, display, &processor, &config, listener)?;
 
     if let Err(e) = WaylandSource::new(queue).quick_insert(event_handle) {
         return Err(e.to_string());
@@ -431,29 +632,57 @@ fn main_loop(
         }
     }
     let mut loop_signal = event_loop.get_signal();
+    // outputs may also be removed outside of this loop, when they are destroyed
+    let mut output_count = bgs.borrow().len();
     if let Err(e) = event_loop.run(None, &mut loop_signal, |_| {
         {
             let mut bgs = bgs.borrow_mut();
             let mut i = 0;
+            let mut changed = output_count != bgs.len();
             while i != bgs.len() {
+                let moved = bgs[i].update_position();
+                changed |= moved;
                 if let Some(should_remove) = bgs[i].handle_events() {
+                    changed = true;
                     let mut processor = processor.borrow_mut();
                     processor.set_output_count(bgs.len() as u8);
                     processor.stop_animations(&[bgs[i].info.name.clone()]);
                     if should_remove {
-                        bgs.remove(i);
+                        let bg = bgs.remove(i);
+                        processor
+                            .events()
+                            .send(Event::OutputRemoved(bg.info.name.clone()));
                     } else {
+                        processor
+                            .events()
+                            .send(Event::OutputReconfigured(bgs[i].info.clone()));
                         let info = bgs[i].info.clone();
                         let old_img = bgs[i].get_current_img_mut();
-                        if let Some(path) = processor.import_cached_img(info, old_img) {
-                            bgs[i].info.img = BgImg::Img(path);
-                        }
+                        // configuring the output cleared it
+                        let img = match processor.import_cached_img(info, old_img) {
+                            Some(path) => BgImg::Img(path),
+                            None => bgs[i].info.img.clone(),
+                        };
+                        bgs[i].set_img(img, processor.events());
                         i += 1;
                     }
                 } else {
+                    if moved {
+                        processor
+                            .borrow()
+                            .events()
+                            .send(Event::OutputReconfigured(bgs[i].info.clone()));
+                    }
                     i += 1;
                 }
             }
+            output_count = bgs.len();
+            if changed {
+                let infos: Vec<BgInfo> = bgs.iter().map(|bg| bg.info.clone()).collect();
+                let mut processor = processor.borrow_mut();
+                processor.update_playlists(&infos);
+                processor.update_spans(&infos);
+            }
         }
         if let Err(e) = display.flush() {
             error!("Couldn't flush display: {}", e);
@@ -470,70 +699,182 @@ fn recv_socket_msg(
     stream: UnixStream,
     loop_signal: &calloop::LoopSignal,
     proc: &mut Processor,
+    config: &Result<Config, String>,
 ) -> Result<(), String> {
     let request = Request::receive(&stream);
     let answer = match request {
-        Ok(Request::Animation(animations)) => {
-            let mut result = Answer::Ok;
-            for animation in &animations {
-                for output in &animation.1 {
-                    if !bgs.iter().any(|bg| &bg.info.name == output) {
-                        result = Answer::Err(format!("Output {output} doesn't exit"));
-                        break;
-                    }
+        Ok(Request::AnimationStream(animations)) => {
+            match animations
+                .iter()
+                .flat_map(|animation| &animation.outputs)
+                .find(|output| !bgs.iter().any(|bg| &bg.info.name == *output))
+            {
+                Some(output) => Answer::Err(format!("Output {output} doesn't exist")),
+                None => {
+                    let animations = animations
+                        .into_iter()
+                        .map(|animation| {
+                            // all outputs of an animation have the same dimensions
+                            let size = bgs
+                                .iter()
+                                .find(|bg| animation.outputs.contains(&bg.info.name))
+                                .map_or(0, |bg| {
+                                    let dim = bg.info.real_dim();
+                                    dim.0 as usize * dim.1 as usize * 4
+                                });
+                            (animation, size)
+                        })
+                        .collect();
+                    proc.stream_animations(&stream, animations)
                 }
             }
-            if matches!(result, Answer::Err(_)) {
-                result
-            } else {
-                for animation in animations {
-                    let bg = bgs.iter().find(|bg| animation.1.contains(&bg.info.name));
-                    if bg.is_none() {
-                        continue;
-                    }
-                    //unwraping is fine because we test it above
-                    let dim = bg.unwrap().info.real_dim();
-                    let size = dim.0 as usize * dim.1 as usize * 4;
-                    if let Answer::Err(e) = proc.animate(animation.0, animation.1, size) {
-                        result = Answer::Err(e);
-                    }
-                }
-                result
-            }
         }
-        Ok(Request::Clear(clear)) => clear_outputs(&mut bgs, &clear, proc),
+        Ok(Request::Clear(clear)) => {
+            proc.stop_playlists(&clear.outputs, &get_infos(&bgs));
+            proc.stop_spans(&clear.outputs);
+            clear_outputs(&mut bgs, clear, proc)
+        }
+        Ok(Request::Restore(restore)) => restore_outputs(&mut bgs, restore, proc),
         Ok(Request::Kill) => {
             loop_signal.stop();
             Answer::Ok
         }
-        Ok(Request::Img(img)) => {
-            let old_imgs = get_old_imgs(&mut bgs, &img.1);
-            if old_imgs.len() != img.1.len() {
-                Answer::Err("Daemon received request for outputs that don't exist".to_string())
-            } else {
-                proc.transition(&img.0, img.1, old_imgs)
-            }
+        Ok(Request::Img(requests)) => img_request(&mut bgs, requests, proc),
+        Ok(Request::Playlist(playlist)) => proc.playlist(playlist, &get_infos(&bgs)),
+        Ok(Request::PlaylistControl(control)) => proc.playlist_control(control),
+        Ok(Request::Ping) => Answer::Ping(Ping {
+            protocol: PROTOCOL_VERSION,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            capabilities: CAPABILITIES.iter().map(ToString::to_string).collect(),
+        }),
+        Ok(Request::Config) => match config {
+            Ok(config) => Answer::Config(config.clone()),
+            Err(e) => Answer::Err(format!("failed to load config file: {e}")),
+        },
+        Ok(Request::Query) => {
+            let mut infos = get_infos(&bgs);
+            proc.set_activity(&mut infos);
+            Answer::Info(infos)
         }
-        Ok(Request::Init { .. }) => Answer::Ok,
-        Ok(Request::Query) => Answer::Info(bgs.iter().map(|bg| bg.info.clone()).collect()),
+        Ok(Request::Subscribe) => {
+            // the events must only start coming after the answer
+            Answer::Ok.send(&stream)?;
+            return proc.events().subscribe(stream);
+        }
+        Ok(Request::Wait(outputs)) => match outputs
+            .iter()
+            .find(|output| !bgs.iter().any(|bg| &bg.info.name == *output))
+        {
+            Some(output) => Answer::Err(format!("Output {output} doesn't exist")),
+            None => match proc.wait_transitions(&stream, outputs) {
+                // the waiting thread answers once the transitions are done
+                Ok(()) => return Ok(()),
+                Err(e) => Answer::Err(e),
+            },
+        },
         Err(e) => Answer::Err(e),
     };
     answer.send(&stream)
 }
 
-fn get_old_imgs(bgs: &mut RefMut<Vec<Bg>>, imgs: &[(Img, Vec<String>)]) -> Vec<ImgWithDim> {
-    let mut v = Vec::with_capacity(imgs.len());
+fn get_infos(bgs: &RefMut<Vec<Bg>>) -> Vec<BgInfo> {
+    bgs.iter().map(|bg| bg.info.clone()).collect()
+}
 
+/// `get_old_imgs` assumes all outputs in a group are displaying the same image. When that is not
+/// the case, we split the group so that every output transitions from its own image
+fn split_by_current_img(
+    bgs: &RefMut<Vec<Bg>>,
+    imgs: Vec<(Img, Vec<String>)>,
+) -> Vec<(Img, Vec<String>)> {
+    let mut v = Vec::with_capacity(imgs.len());
     for (img, outputs) in imgs {
+        let mut groups: Vec<(&BgImg, Vec<String>)> = Vec::new();
+        for bg in bgs.iter().filter(|bg| outputs.contains(&bg.info.name)) {
+            match groups
+                .iter_mut()
+                .find(|(current, _)| **current == bg.info.img)
+            {
+                Some((_, names)) => names.push(bg.info.name.clone()),
+                None => groups.push((&bg.info.img, vec![bg.info.name.clone()])),
+            }
+        }
+        if let Some((_, last)) = groups.pop() {
+            for (_, names) in groups {
+                v.push((
+                    Img {
+                        path: img.path.clone(),
+                        img: img.img.clone(),
+                        span: img.span.clone(),
+                    },
+                    names,
+                ));
+            }
+            v.push((img, last));
+        }
+    }
+    v
+}
+
+/// Displays every group of images, each with its own transition. Either all of them start, or none
+/// does
+fn img_request(
+    bgs: &mut RefMut<Vec<Bg>>,
+    requests: Vec<ImageRequest>,
+    proc: &mut Processor,
+) -> Answer {
+    let outputs: Vec<String> = requests
+        .iter()
+        .flat_map(|(_, imgs)| imgs.iter().flat_map(|(_, o)| o.clone()))
+        .collect();
+    if outputs
+        .iter()
+        .any(|output| !bgs.iter().any(|bg| &bg.info.name == output))
+    {
+        return Answer::Err("Daemon received request for outputs that don't exist".to_string());
+    }
+
+    let mut groups = Vec::with_capacity(requests.len());
+    for (transition, imgs) in requests {
+        let old_imgs = get_old_imgs(bgs, &imgs);
+        // like when an output was reconfigured after the client queried it
+        if let Err(e) = processor::check_sizes(&imgs, &old_imgs) {
+            return Answer::Err(e);
+        }
+        groups.push((transition, imgs, old_imgs));
+    }
+
+    let infos = get_infos(bgs);
+    proc.stop_playlists(&outputs, &infos);
+    proc.stop_spans(&outputs);
+    let mut answer = Answer::Ok;
+    for (transition, imgs, old_imgs) in groups {
+        set_imgs(bgs, &imgs, proc.events());
+        proc.add_spans(&transition, &imgs, &infos);
+        if let Answer::Err(e) = proc.transition(&transition, imgs, old_imgs) {
+            answer = Answer::Err(e);
+        }
+    }
+    answer
+}
+
+fn get_old_imgs(bgs: &mut RefMut<Vec<Bg>>, imgs: &[(Img, Vec<String>)]) -> Vec<ImgWithDim> {
+    let mut v = Vec::with_capacity(imgs.len());
+    for (_, outputs) in imgs {
         if let Some(bg) = bgs.iter_mut().find(|bg| bg.info.name == outputs[0]) {
             v.push((bg.get_current_img().into(), bg.info.real_dim()));
         }
+    }
+    v
+}
+
+/// Must only be called once we are sure the images will be displayed
+fn set_imgs(bgs: &mut RefMut<Vec<Bg>>, imgs: &[(Img, Vec<String>)], events: &Events) {
+    for (img, outputs) in imgs {
         for bg in bgs.iter_mut().filter(|bg| outputs.contains(&bg.info.name)) {
-            bg.info.img = BgImg::Img(img.path.clone());
+            bg.set_img(BgImg::Img(img.path.clone()), events);
         }
     }
-
-    v
 }
 
 fn handle_recv_img(bgs: &mut RefMut<Vec<Bg>>, msg: &(Vec<String>, ReadiedPack)) {
@@ -546,15 +887,112 @@ fn handle_recv_img(bgs: &mut RefMut<Vec<Bg>>, msg: &(Vec<String>, ReadiedPack))
         .for_each(|bg| bg.draw(img));
 }
 
-//TODO: error when no output was valid
-fn clear_outputs(bgs: &mut RefMut<Vec<Bg>>, clear: &Clear, proc: &mut Processor) -> Answer {
-    proc.stop_animations(&clear.outputs);
-    if clear.outputs.is_empty() {
-        bgs.iter_mut().for_each(|bg| bg.clear(clear.color));
+/// Transitions to the cached wallpapers of the outputs, animating them if necessary
+fn restore_outputs(bgs: &mut RefMut<Vec<Bg>>, restore: Restore, proc: &mut Processor) -> Answer {
+    let outputs = if restore.outputs.is_empty() {
+        bgs.iter().map(|bg| bg.info.name.clone()).collect()
     } else {
-        bgs.iter_mut()
-            .filter(|bg| clear.outputs.contains(&bg.info.name))
-            .for_each(|bg| bg.clear(clear.color));
+        restore.outputs
+    };
+    if !restore.files.is_empty() && restore.files.len() != outputs.len() {
+        return Answer::Err("Every output to restore must have its own file".to_string());
+    }
+
+    let mut imgs = Vec::with_capacity(outputs.len());
+    let mut animations = Vec::new();
+    for (i, output) in outputs.into_iter().enumerate() {
+        let size = match bgs.iter().find(|bg| bg.info.name == output) {
+            Some(bg) => {
+                let dim = bg.info.real_dim();
+                dim.0 as usize * dim.1 as usize * 4
+            }
+            None => return Answer::Err(format!("Output {output} doesn't exist")),
+        };
+        let cached = match restore.files.get(i) {
+            Some(file) => processor::read_cached_bg(file),
+            None => processor::get_cached_bg(&output),
+        };
+        let (img, animation) = match cached {
+            Some(cached) => cached,
+            None => return Answer::Err(format!("There is no cached wallpaper for {output}")),
+        };
+        if img.img.len() != size {
+            return Answer::Err(format!(
+                "The cached wallpaper for {output} was made for a different resolution"
+            ));
+        }
+        if let Some(animation) = animation {
+            animations.push((animation, vec![output.clone()], size));
+        }
+        imgs.push((img, vec![output]));
+    }
+
+    let outputs: Vec<String> = imgs.iter().flat_map(|(_, o)| o.clone()).collect();
+    let infos = get_infos(bgs);
+    proc.stop_playlists(&outputs, &infos);
+    proc.stop_spans(&outputs);
+    // only span the images again if we are restoring every output they span
+    let spanned: Vec<(Img, Vec<String>)> = imgs
+        .iter()
+        .filter(|(img, _)| {
+            img.span
+                .as_ref()
+                .is_some_and(|span| span.outputs.iter().all(|o| outputs.contains(o)))
+        })
+        .map(|(img, outputs)| {
+            (
+                Img {
+                    path: img.path.clone(),
+                    img: Vec::new(),
+                    span: img.span.clone(),
+                },
+                outputs.clone(),
+            )
+        })
+        .collect();
+    proc.add_spans(&restore.transition, &spanned, &infos);
+
+    let old_imgs = get_old_imgs(bgs, &imgs);
+    if let Err(e) = processor::check_sizes(&imgs, &old_imgs) {
+        return Answer::Err(e);
+    }
+    set_imgs(bgs, &imgs, proc.events());
+    let mut answer = proc.transition(&restore.transition, imgs, old_imgs);
+    for (animation, outputs, size) in animations {
+        if let Answer::Err(e) = proc.animate(animation, outputs, size) {
+            answer = Answer::Err(e);
+        }
+    }
+    answer
+}
+
+//TODO: error when no output was valid
+/// Transitions to the color (or gradient), rendered at every output's real dimensions
+fn clear_outputs(bgs: &mut RefMut<Vec<Bg>>, clear: Clear, proc: &mut Processor) -> Answer {
+    let mut imgs = Vec::new();
+    let mut old_imgs = Vec::new();
+    for bg in bgs
+        .iter_mut()
+        .filter(|bg| clear.outputs.is_empty() || clear.outputs.contains(&bg.info.name))
+    {
+        let dim = bg.info.real_dim();
+        let img = match &clear.gradient {
+            Some(gradient) => gradient::render(gradient, dim),
+            None => [clear.color[2], clear.color[1], clear.color[0], 255]
+                .repeat(dim.0 as usize * dim.1 as usize),
+        };
+        old_imgs.push((bg.get_current_img().into(), dim));
+        let bg_img = match &clear.gradient {
+            Some(gradient) => BgImg::Gradient(gradient.clone()),
+            None => BgImg::Color(clear.color),
+        };
+        bg.set_img(bg_img, proc.events());
+        let img = Img {
+            path: PathBuf::new(),
+            img,
+            span: None,
+        };
+        imgs.push((img, vec![bg.info.name.clone()]));
     }
-    Answer::Ok
+    proc.transition(&clear.transition, imgs, old_imgs)
 }
@@ -83,6 +83,7 @@ impl Transition {
         debug!("Starting transition");
         match self.transition_type {
             TransitionType::Simple => self.simple(new_img, outputs, sender, stop_recv),
+            TransitionType::Fade => self.fade(new_img, outputs, sender, stop_recv),
             TransitionType::Wipe => self.wipe(new_img, outputs, sender, stop_recv),
             TransitionType::Grow => self.grow(new_img, outputs, sender, stop_recv),
             TransitionType::Outer => self.outer(new_img, outputs, sender, stop_recv),
@@ -116,6 +117,43 @@ impl Transition {
         }
     }
 
+    /// Unlike `simple`, every pixel goes from the old image to the new one at the same pace, so
+    /// the transition takes exactly `duration`, however different the images are
+    fn fade(
+        mut self,
+        new_img: &[u8],
+        outputs: &mut Vec<String>,
+        sender: &SyncSender<(Vec<String>, ReadiedPack)>,
+        stop_recv: &mpsc::Receiver<Vec<String>>,
+    ) {
+        let fps = self.fps;
+        let start_img = self.old_img.clone();
+        let mut now = Instant::now();
+
+        let (mut seq, start) = self.bezier_seq(0.0, 1.0);
+
+        loop {
+            seq.advance_to(start.elapsed().as_secs_f64());
+            let progress = seq.now();
+            let transition_img =
+                ReadiedPack::new(&mut self.old_img, new_img, |old_pix, new_pix, i| {
+                    let start_pix = &start_img[i * 4..i * 4 + 4];
+                    for ((old_col, start_col), new_col) in
+                        old_pix.iter_mut().zip(start_pix).zip(new_pix)
+                    {
+                        *old_col = mix(*start_col, *new_col, progress);
+                    }
+                });
+            send_transition_frame!(transition_img, outputs, now, fps, sender, stop_recv);
+            now = Instant::now();
+            if start.elapsed().as_secs_f64() >= seq.duration() {
+                break;
+            }
+        }
+        self.step = 255;
+        self.simple(new_img, outputs, sender, stop_recv)
+    }
+
     fn wave(
         mut self,
         new_img: &[u8],
@@ -367,6 +405,14 @@ impl Transition {
     }
 }
 
+/// The color `progress` of the way from `from` to `to`
+fn mix(from: u8, to: u8, progress: f32) -> u8 {
+    let from = f32::from(from);
+    (from + (f32::from(to) - from) * progress)
+        .round()
+        .clamp(0.0, 255.0) as u8
+}
+
 fn change_cols(step: u8, old: &mut [u8; 4], new: [u8; 4]) {
     for (old_col, new_col) in old.iter_mut().zip(new) {
         if old_col.abs_diff(new_col) < step {
@@ -431,7 +477,14 @@ mod tests {
     #[test]
     fn transitions_should_end_with_equal_vectors() {
         use TransitionType as TT;
-        let transitions = [TT::Simple, TT::Wipe, TT::Outer, TT::Grow, TT::Wave];
+        let transitions = [
+            TT::Simple,
+            TT::Fade,
+            TT::Wipe,
+            TT::Outer,
+            TT::Grow,
+            TT::Wave,
+        ];
         for transition in transitions {
             let ((fr_send, fr_recv), (_stop_send, stop_recv)) = make_senders_and_receivers();
             let (old_img, new_img) = make_test_boxes();
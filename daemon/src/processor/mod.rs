@@ -1,9 +1,11 @@
 use log::{debug, error, info};
 
-use smithay_client_toolkit::reexports::calloop::channel::SyncSender;
+use smithay_client_toolkit::reexports::calloop::channel::{Sender, SyncSender};
 
 use std::{
-    path::PathBuf,
+    io::BufReader,
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
     sync::mpsc,
     sync::{Arc, RwLock},
     thread,
@@ -11,35 +13,83 @@ use std::{
 };
 
 use utils::{
-    communication::{Animation, Answer, BgInfo, Img},
-    comp_decomp::ReadiedPack,
+    communication::{
+        self, Animation, AnimationFrame, Answer, BgInfo, Event, ImageRequest, Img, PlaylistCommand,
+        PlaylistControl, ResizeStrategy, StreamedAnimation, Transition,
+    },
+    comp_decomp::{BitPack, ReadiedPack},
 };
 
 mod animations;
+mod on_going_transitions;
+mod playlist;
+mod span;
 mod sync_barrier;
 
+use crate::events::Events;
+use on_going_transitions::OnGoingTransitions;
+use playlist::{OutputGroups, PlaylistMsg};
+
 ///The default thread stack size of 2MiB is way too overkill for our purposes
-const TSTACK_SIZE: usize = 1 << 17; //128KiB
+pub const TSTACK_SIZE: usize = 1 << 17; //128KiB
 
 pub type ImgWithDim = (Box<[u8]>, (u32, u32));
 
+/// The frames of an animation, as they become available. `None` marks the end of the animation,
+/// while disconnecting without sending it means the animation was interrupted
+type Frames = mpsc::Receiver<Option<(BitPack, Duration)>>;
+
+/// What playlists and spans send through `img_sender`. Images from playlists come with the
+/// playlist's id, since they may still be on their way once it was stopped
+pub type ProcessedImg = (Option<u64>, ImageRequest);
+
+struct PlaylistHandle {
+    id: u64,
+    outputs: Vec<String>,
+    sender: mpsc::Sender<PlaylistMsg>,
+}
+
 pub struct Processor {
     frame_sender: SyncSender<(Vec<String>, ReadiedPack)>,
+    /// Used by playlists and spans to send the images they process by themselves
+    img_sender: Sender<ProcessedImg>,
     anim_stoppers: Vec<mpsc::Sender<Vec<String>>>,
-    on_going_transitions: Arc<RwLock<Vec<String>>>,
+    playlists: Vec<PlaylistHandle>,
+    /// The id of the next playlist to start
+    next_playlist_id: u64,
+    spans: Vec<span::Spanned>,
+    /// Outputs currently playing an animation
+    animating: Arc<RwLock<Vec<String>>>,
+    on_going_transitions: Arc<OnGoingTransitions>,
     sync_barrier: Arc<sync_barrier::SyncBarrier>,
+    events: Events,
 }
 
 impl Processor {
-    pub fn new(frame_sender: SyncSender<(Vec<String>, ReadiedPack)>) -> Self {
+    pub fn new(
+        frame_sender: SyncSender<(Vec<String>, ReadiedPack)>,
+        img_sender: Sender<ProcessedImg>,
+        events: Events,
+    ) -> Self {
         Self {
             frame_sender,
+            img_sender,
             anim_stoppers: Vec::new(),
-            on_going_transitions: Arc::new(RwLock::new(Vec::new())),
+            playlists: Vec::new(),
+            next_playlist_id: 0,
+            spans: Vec::new(),
+            animating: Arc::new(RwLock::new(Vec::new())),
+            on_going_transitions: Arc::new(OnGoingTransitions::new()),
             sync_barrier: Arc::new(sync_barrier::SyncBarrier::new(0)),
+            events,
         }
     }
 
+    /// Where to send the events subscribers are told about
+    pub fn events(&self) -> &Events {
+        &self.events
+    }
+
     pub fn set_output_count(&mut self, outputs_count: u8) {
         self.sync_barrier.set_goal(outputs_count);
     }
@@ -50,43 +100,48 @@ impl Processor {
         requests: Vec<(Img, Vec<String>)>,
         old_imgs: Vec<ImgWithDim>,
     ) -> Answer {
+        // check everything before starting, so we never start only some of the transitions
+        if let Err(e) = check_sizes(&requests, &old_imgs) {
+            return Answer::Err(e);
+        }
+
         let mut answer = Answer::Ok;
         for ((old_img, dim), (new_img, mut outputs)) in old_imgs.into_iter().zip(requests) {
-            if old_img.len() != new_img.img.len() {
-                return Answer::Err(format!(
-                    "Output and image have different sizes: {} vs {}.\
-                            This should be impossible.\
-                            Please get in the contact with the developers",
-                    old_img.len(),
-                    new_img.img.len()
-                ));
-            }
             self.stop_animations(&outputs);
             let transition = transition.clone();
             let sender = self.frame_sender.clone();
             let (stopper, stop_recv) = mpsc::channel();
             self.anim_stoppers.push(stopper);
             let on_going_transitions = Arc::clone(&self.on_going_transitions);
+            // mark the outputs before spawning the thread, so that animations sent right after this
+            // are sure to wait for the transition
+            let id = on_going_transitions.start(&outputs);
+            for output in &outputs {
+                self.events.send(Event::TransitionStarted(output.clone()));
+            }
+            let marked = outputs.clone();
+            let thread_on_going_transitions = Arc::clone(&on_going_transitions);
+            let events = self.events.clone();
             if let Err(e) = thread::Builder::new()
                 .name("transition".to_string()) //Name our threads  for better log messages
                 .stack_size(TSTACK_SIZE) //the default of 2MB is way too overkill for this
                 .spawn(move || {
-                    on_going_transitions
-                        .write()
-                        .unwrap()
-                        .extend_from_slice(&outputs);
+                    // `execute` removes the outputs whose transitions were stopped midway. Those
+                    // were unmarked by whoever stopped them, and may be running newer transitions
                     animations::Transition::new(old_img, dim, transition).execute(
                         &new_img.img,
                         &mut outputs,
                         &sender,
                         &stop_recv,
                     );
-                    on_going_transitions
-                        .write()
-                        .unwrap()
-                        .retain(|output| !outputs.contains(output));
+                    for output in thread_on_going_transitions.finish(&outputs, id) {
+                        events.send(Event::TransitionFinished(output));
+                    }
                 })
             {
+                for output in on_going_transitions.finish(&marked, id) {
+                    self.events.send(Event::TransitionFinished(output));
+                }
                 answer = Answer::Err(format!("failed to spawn transition thread: {e}"));
                 error!("failed to spawn 'transition' thread: {}", e);
             };
@@ -94,9 +149,89 @@ impl Processor {
         answer
     }
 
+    /// Plays a complete animation, like the ones in the cache
     pub fn animate(
         &mut self,
-        animation: utils::communication::Animation,
+        animation: Animation,
+        outputs: Vec<String>,
+        output_size: usize,
+    ) -> Answer {
+        let (sender, frames) = mpsc::channel();
+        for frame in animation.animation.into_vec() {
+            let _ = sender.send(Some(frame));
+        }
+        let _ = sender.send(None);
+        self.play(frames, animation.sync, outputs, output_size)
+    }
+
+    /// Starts playing animations whose frames are still being made by the client. They are read
+    /// from `stream` in a separate thread, as they arrive. `animations` holds the size of the
+    /// buffers of every animation's outputs
+    pub fn stream_animations(
+        &mut self,
+        stream: &UnixStream,
+        animations: Vec<(StreamedAnimation, usize)>,
+    ) -> Answer {
+        let stream = match stream.try_clone() {
+            Ok(stream) => stream,
+            Err(e) => return Answer::Err(format!("failed to clone animation stream: {e}")),
+        };
+
+        let mut answer = Answer::Ok;
+        let mut senders = Vec::with_capacity(animations.len());
+        for (animation, output_size) in animations {
+            let (sender, frames) = mpsc::channel();
+            senders.push(Some(sender));
+            if let Answer::Err(e) =
+                self.play(frames, animation.sync, animation.outputs, output_size)
+            {
+                answer = Answer::Err(e);
+            }
+        }
+
+        if let Err(e) = thread::Builder::new()
+            .name("animation stream".to_string()) //Name our threads  for better log messages
+            .stack_size(TSTACK_SIZE) //the default of 2MB is way too overkill for this
+            .spawn(move || {
+                let mut reader = BufReader::new(stream);
+                while senders.iter().any(Option::is_some) {
+                    match AnimationFrame::receive(&mut reader) {
+                        Ok(AnimationFrame::Frame {
+                            animation,
+                            frame,
+                            duration,
+                        }) => {
+                            if let Some(Some(sender)) = senders.get(animation) {
+                                // the animation may have been stopped already
+                                let _ = sender.send(Some((frame, duration)));
+                            }
+                        }
+                        Ok(AnimationFrame::End(animation)) => {
+                            if let Some(sender) = senders.get_mut(animation).and_then(Option::take)
+                            {
+                                let _ = sender.send(None);
+                            }
+                        }
+                        Err(e) => {
+                            error!("animation stream was interrupted: {e}");
+                            return;
+                        }
+                    }
+                }
+            })
+        {
+            answer = Answer::Err(format!("failed to spawn animation stream thread: {e}"));
+            error!("failed to spawn 'animation stream' thread: {e}");
+        }
+        answer
+    }
+
+    /// Plays the animation in `frames` until it is stopped. Frames that did not arrive yet are
+    /// waited for, and the animation only loops once it is complete
+    fn play(
+        &mut self,
+        frames: Frames,
+        sync: bool,
         mut outputs: Vec<String>,
         output_size: usize,
     ) -> Answer {
@@ -105,30 +240,48 @@ impl Processor {
         let sender = self.frame_sender.clone();
         let (stopper, stop_recv) = mpsc::channel();
         let on_going_transitions = Arc::clone(&self.on_going_transitions);
+        let animating = Arc::clone(&self.animating);
+        let events = self.events.clone();
 
         let barrier = Arc::clone(&self.sync_barrier);
         self.anim_stoppers.push(stopper);
+        mark_animating(&animating, &outputs, &events);
         if let Err(e) = thread::Builder::new()
             .name("animation".to_string()) //Name our threads  for better log messages
             .stack_size(TSTACK_SIZE) //the default of 2MB is way too overkill for this
             .spawn(move || {
-                while on_going_transitions
-                    .read()
-                    .unwrap()
-                    .iter()
-                    .any(|output| outputs.contains(output))
-                {
-                    std::thread::yield_now();
-                }
+                on_going_transitions.wait_for(&outputs);
+                let mut animation = Vec::new();
+                let mut complete = false;
+                let mut i = 0;
                 let mut now = std::time::Instant::now();
-                /* We only need to animate if we have > 1 frame */
-                if animation.animation.len() == 1 {
-                    return;
-                }
-                for (frame, duration) in animation.animation.iter().cycle() {
+                loop {
+                    /* We only need to animate if we have > 1 frame */
+                    if !complete && (i == animation.len() || animation.len() < 2) {
+                        match recv_frame(&frames, &mut outputs, &stop_recv) {
+                            Received::Frame(frame) => animation.push(frame),
+                            Received::End => complete = true,
+                            Received::Stopped => return,
+                            Received::Interrupted => {
+                                unmark_animating(&animating, &outputs, &events);
+                                return;
+                            }
+                        }
+                        continue;
+                    }
+                    if animation.len() < 2 {
+                        unmark_animating(&animating, &outputs, &events);
+                        return;
+                    }
+                    if i == animation.len() {
+                        i = 0;
+                    }
+
+                    let (frame, duration) = &animation[i];
+                    i += 1;
                     let frame = frame.ready(output_size);
 
-                    if animation.sync {
+                    if sync {
                         barrier.inc_and_wait_while(*duration, || match stop_recv.try_recv() {
                             Ok(to_remove) => {
                                 outputs.retain(|o| !to_remove.contains(o));
@@ -159,17 +312,246 @@ impl Processor {
     }
 
     pub fn stop_animations(&mut self, to_stop: &[String]) {
-        self.on_going_transitions
-            .write()
-            .unwrap()
-            .retain(|output| !to_stop.contains(output));
+        if to_stop.is_empty() {
+            let all = self.animating.read().unwrap().clone();
+            unmark_animating(&self.animating, &all, &self.events);
+        } else {
+            unmark_animating(&self.animating, to_stop, &self.events);
+        }
+        for output in self.on_going_transitions.stop(to_stop) {
+            self.events.send(Event::TransitionFinished(output));
+        }
         self.anim_stoppers
             .retain(|a| a.send(to_stop.to_vec()).is_ok());
     }
 
+    /// Starts a playlist in the requested outputs (or all of them, if none were requested).
+    /// Any playlist that was running in those outputs is stopped
+    pub fn playlist(&mut self, mut request: communication::Playlist, infos: &[BgInfo]) -> Answer {
+        if request.outputs.is_empty() {
+            request.outputs = infos.iter().map(|info| info.name.clone()).collect();
+        } else if let Some(output) = request
+            .outputs
+            .iter()
+            .find(|output| !infos.iter().any(|info| &info.name == *output))
+        {
+            return Answer::Err(format!("Output {output} doesn't exist"));
+        }
+        if let ResizeStrategy::Span { .. } = request.resize.strategy {
+            return Answer::Err("playlists can not span images across outputs".to_string());
+        }
+
+        self.stop_playlists(&request.outputs, infos);
+        self.stop_spans(&request.outputs);
+        let outputs = request.outputs.clone();
+        let id = self.next_playlist_id;
+        let playlist = match playlist::Playlist::new(id, request, group_outputs(&outputs, infos)) {
+            Ok(playlist) => playlist,
+            Err(e) => return Answer::Err(e),
+        };
+        self.next_playlist_id += 1;
+
+        let sender = self.img_sender.clone();
+        let (msg_sender, msg_recv) = mpsc::channel();
+        if let Err(e) = thread::Builder::new()
+            .name("playlist".to_string()) //Name our threads  for better log messages
+            .stack_size(TSTACK_SIZE) //the default of 2MB is way too overkill for this
+            .spawn(move || playlist.run(sender, msg_recv))
+        {
+            error!("failed to spawn 'playlist' thread: {e}");
+            return Answer::Err(format!("failed to spawn playlist thread: {e}"));
+        }
+        self.playlists.push(PlaylistHandle {
+            id,
+            outputs,
+            sender: msg_sender,
+        });
+        Answer::Ok
+    }
+
+    /// Sends a command to the playlists running in the requested outputs (or all of them, if
+    /// none were requested)
+    pub fn playlist_control(&mut self, control: PlaylistControl) -> Answer {
+        let mut found = false;
+        for handle in self.playlists.iter().filter(|handle| {
+            control.outputs.is_empty() || handle.outputs.iter().any(|o| control.outputs.contains(o))
+        }) {
+            found |= handle
+                .sender
+                .send(PlaylistMsg::Command(control.command))
+                .is_ok();
+        }
+
+        if control.command == PlaylistCommand::Stop {
+            self.playlists.retain(|handle| {
+                !control.outputs.is_empty()
+                    && !handle.outputs.iter().any(|o| control.outputs.contains(o))
+            });
+        }
+
+        if found {
+            Answer::Ok
+        } else {
+            Answer::Err("there is no playlist running in the requested outputs".to_string())
+        }
+    }
+
+    /// Removes the outputs from the playlists they are part of. Playlists left without outputs
+    /// stop running
+    pub fn stop_playlists(&mut self, to_stop: &[String], infos: &[BgInfo]) {
+        for handle in self.playlists.iter_mut() {
+            let len = handle.outputs.len();
+            handle
+                .outputs
+                .retain(|output| !to_stop.is_empty() && !to_stop.contains(output));
+            if handle.outputs.len() != len {
+                let _ = handle
+                    .sender
+                    .send(PlaylistMsg::Outputs(group_outputs(&handle.outputs, infos)));
+            }
+        }
+        self.playlists.retain(|handle| !handle.outputs.is_empty());
+    }
+
+    /// The outputs the playlist `id` is still running in. Empty once it was stopped
+    #[must_use]
+    pub fn playlist_outputs(&self, id: u64) -> &[String] {
+        match self.playlists.iter().find(|handle| handle.id == id) {
+            Some(handle) => &handle.outputs,
+            None => &[],
+        }
+    }
+
+    /// Must be called whenever outputs are reconfigured or removed, so that the playlists
+    /// always resize their images to the correct dimensions
+    pub fn update_playlists(&mut self, infos: &[BgInfo]) {
+        for handle in self.playlists.iter_mut() {
+            handle
+                .outputs
+                .retain(|output| infos.iter().any(|info| &info.name == output));
+            let groups = group_outputs(&handle.outputs, infos);
+            let _ = handle.sender.send(PlaylistMsg::Outputs(groups));
+        }
+        self.playlists.retain(|handle| !handle.outputs.is_empty());
+    }
+
+    /// Answers through `stream` once none of `outputs` (or no output at all, if it is empty) is
+    /// transitioning. The waiting happens in a separate thread, so that the daemon keeps going
+    pub fn wait_transitions(
+        &self,
+        stream: &UnixStream,
+        outputs: Vec<String>,
+    ) -> Result<(), String> {
+        let stream = match stream.try_clone() {
+            Ok(stream) => stream,
+            Err(e) => return Err(format!("failed to clone wait stream: {e}")),
+        };
+        let on_going_transitions = Arc::clone(&self.on_going_transitions);
+        if let Err(e) = thread::Builder::new()
+            .name("transition waiter".to_string()) //Name our threads  for better log messages
+            .stack_size(TSTACK_SIZE) //the default of 2MB is way too overkill for this
+            .spawn(move || {
+                on_going_transitions.wait_for(&outputs);
+                if let Err(e) = Answer::Ok.send(&stream) {
+                    error!("failed to answer wait request: {e}");
+                }
+            })
+        {
+            error!("failed to spawn 'transition waiter' thread: {e}");
+            return Err(format!("failed to spawn transition waiter thread: {e}"));
+        }
+        Ok(())
+    }
+
+    /// Fills in what each output is currently doing
+    pub fn set_activity(&self, infos: &mut [BgInfo]) {
+        let animating = self.animating.read().unwrap();
+        for info in infos {
+            info.animating = animating.contains(&info.name);
+            info.transitioning = self.on_going_transitions.contains(&info.name);
+        }
+    }
+
+    /// Starts keeping track of the spanned images in `imgs`, so we can span them again when their
+    /// outputs change
+    pub fn add_spans(
+        &mut self,
+        transition: &Transition,
+        imgs: &[(Img, Vec<String>)],
+        infos: &[BgInfo],
+    ) {
+        for (img, _) in imgs {
+            if let Some(span) = &img.span {
+                if !self.spans.iter().any(|s| s.span.outputs == span.outputs) {
+                    self.spans.push(span::Spanned::new(
+                        span.clone(),
+                        img.path.clone(),
+                        transition.clone(),
+                        infos,
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Forgets the spans that include any of the outputs (or all of them, if `to_stop` is empty).
+    /// The outputs keep displaying their part of the image, but it will no longer be spanned again
+    pub fn stop_spans(&mut self, to_stop: &[String]) {
+        self.spans.retain(|spanned| {
+            !to_stop.is_empty() && !spanned.span.outputs.iter().any(|o| to_stop.contains(o))
+        });
+    }
+
+    /// Must be called whenever outputs are moved, reconfigured or removed. Spans whose layout
+    /// changed are spanned again in a separate thread
+    pub fn update_spans(&mut self, infos: &[BgInfo]) {
+        self.spans.retain(|spanned| {
+            spanned
+                .span
+                .outputs
+                .iter()
+                .any(|output| infos.iter().any(|info| &info.name == output))
+        });
+
+        for spanned in self.spans.iter_mut() {
+            let layout = span::layout(&spanned.span.outputs, infos);
+            if layout.is_empty() || layout == spanned.layout {
+                continue;
+            }
+            debug!("Spanning {:?} again", spanned.path);
+            spanned.layout = layout.clone();
+
+            let path = spanned.path.clone();
+            let span = spanned.span.clone();
+            let transition = spanned.transition.clone();
+            let infos = infos.to_vec();
+            let sender = self.img_sender.clone();
+            if let Err(e) = thread::Builder::new()
+                .name("span".to_string()) //Name our threads  for better log messages
+                .stack_size(TSTACK_SIZE) //the default of 2MB is way too overkill for this
+                .spawn(
+                    move || match span::respan(&path, &span, &transition, &layout, &infos) {
+                        Ok(request) => {
+                            match communication::get_cache_path() {
+                                Ok(cache_path) => communication::Request::cache_images(
+                                    &request.1, cache_path, false,
+                                ),
+                                Err(e) => error!("failed to get cache path: {e}"),
+                            }
+                            let _ = sender.send((None, request));
+                        }
+                        Err(e) => error!("failed to span {path:?} again: {e}"),
+                    },
+                )
+            {
+                error!("failed to spawn 'span' thread: {e}");
+            }
+        }
+    }
+
     #[must_use]
     pub fn import_cached_img(&mut self, info: BgInfo, old_img: &mut [u8]) -> Option<PathBuf> {
-        if let Some((Img { img, path }, anim)) = get_cached_bg(&info.name) {
+        if let Some((Img { img, path, .. }, anim)) = get_cached_bg(&info.name) {
             let output_size = old_img.len();
             if output_size < img.len() {
                 info!(
@@ -186,6 +568,13 @@ impl Processor {
             let sender = self.frame_sender.clone();
             let (stopper, stop_recv) = mpsc::channel();
             self.anim_stoppers.push(stopper);
+            if anim.as_ref().is_some_and(|anim| anim.animation.len() > 1) {
+                mark_animating(
+                    &self.animating,
+                    std::slice::from_ref(&info.name),
+                    &self.events,
+                );
+            }
             if let Err(e) = thread::Builder::new()
                 .name("cache importing".to_string()) //Name our threads  for better log messages
                 .stack_size(TSTACK_SIZE) //the default of 2MB is way too overkill for this
@@ -228,6 +617,93 @@ impl Drop for Processor {
     }
 }
 
+/// Marks `outputs` as animating, telling the subscribers about the ones that weren't already
+fn mark_animating(animating: &RwLock<Vec<String>>, outputs: &[String], events: &Events) {
+    let mut animating = animating.write().unwrap();
+    for output in outputs {
+        if !animating.contains(output) {
+            animating.push(output.clone());
+            events.send(Event::AnimationStarted(output.clone()));
+        }
+    }
+}
+
+/// Unmarks `outputs` as animating, telling the subscribers about the ones that were
+fn unmark_animating(animating: &RwLock<Vec<String>>, outputs: &[String], events: &Events) {
+    animating.write().unwrap().retain(|output| {
+        let stopped = outputs.contains(output);
+        if stopped {
+            events.send(Event::AnimationStopped(output.clone()));
+        }
+        !stopped
+    });
+}
+
+/// Groups the outputs by their real dimensions, ignoring the ones that haven't been configured yet
+/// Checks every new image fits the output it is going to, before any of them is displayed
+pub fn check_sizes(requests: &[(Img, Vec<String>)], old_imgs: &[ImgWithDim]) -> Result<(), String> {
+    for ((old_img, _), (new_img, _)) in old_imgs.iter().zip(requests) {
+        if old_img.len() != new_img.img.len() {
+            return Err(format!(
+                "Output and image have different sizes: {} vs {}.\
+                        This should be impossible.\
+                        Please get in the contact with the developers",
+                old_img.len(),
+                new_img.img.len()
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn group_outputs(outputs: &[String], infos: &[BgInfo]) -> OutputGroups {
+    let mut groups: OutputGroups = Vec::new();
+    for info in infos.iter().filter(|info| outputs.contains(&info.name)) {
+        let dim = info.real_dim();
+        if dim.0 == 0 || dim.1 == 0 {
+            continue;
+        }
+        match groups.iter_mut().find(|(d, _)| *d == dim) {
+            Some((_, names)) => names.push(info.name.clone()),
+            None => groups.push((dim, vec![info.name.clone()])),
+        }
+    }
+    groups
+}
+
+enum Received {
+    Frame((BitPack, Duration)),
+    End,
+    Stopped,
+    Interrupted,
+}
+
+/// Waits for the next frame of an animation, while still listening to `stop_recv`
+fn recv_frame(
+    frames: &Frames,
+    outputs: &mut Vec<String>,
+    stop_recv: &mpsc::Receiver<Vec<String>>,
+) -> Received {
+    loop {
+        match frames.recv_timeout(Duration::from_millis(10)) {
+            Ok(Some(frame)) => return Received::Frame(frame),
+            Ok(None) => return Received::End,
+            Err(mpsc::RecvTimeoutError::Timeout) => (),
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Received::Interrupted,
+        }
+        match stop_recv.try_recv() {
+            Ok(to_remove) => {
+                outputs.retain(|o| !to_remove.contains(o));
+                if outputs.is_empty() || to_remove.is_empty() {
+                    return Received::Stopped;
+                }
+            }
+            Err(mpsc::TryRecvError::Empty) => (),
+            Err(mpsc::TryRecvError::Disconnected) => return Received::Stopped,
+        }
+    }
+}
+
 ///Returns whether the calling function should exit or not
 fn send_frame(
     frame: ReadiedPack,
@@ -252,18 +728,21 @@ fn send_frame(
     }
 }
 
-fn get_cached_bg(output: &str) -> Option<(Img, Option<Animation>)> {
-    let cache_path = match utils::communication::get_cache_path() {
+pub fn get_cached_bg(output: &str) -> Option<(Img, Option<Animation>)> {
+    match utils::communication::get_cache_path() {
         Ok(mut path) => {
             path.push(output);
-            path
+            read_cached_bg(&path)
         }
         Err(e) => {
             error!("failed to get bgs cache's path: {e}");
-            return None;
+            None
         }
-    };
+    }
+}
 
+/// Reads a cache file, be it an output's current one or one in its history
+pub fn read_cached_bg(cache_path: &Path) -> Option<(Img, Option<Animation>)> {
     let cache_file = match std::fs::File::open(cache_path) {
         Ok(file) => file,
         Err(e) => {
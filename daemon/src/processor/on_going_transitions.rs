@@ -0,0 +1,128 @@
+use std::sync::{Condvar, Mutex};
+
+///Keeps track of the transition running at each output, so that animations and clients can wait
+///for them to finish. Every transition gets its own id: when a transition is replaced by a new one
+///in the same output, the old one finishing must not unmark the output
+pub struct OnGoingTransitions {
+    state: Mutex<State>,
+    condvar: Condvar,
+}
+
+struct State {
+    next_id: u64,
+    /// Every transitioning output, along with the id of its transition
+    outputs: Vec<(String, u64)>,
+}
+
+impl OnGoingTransitions {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                next_id: 0,
+                outputs: Vec::new(),
+            }),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Marks `outputs` as transitioning, returning the id `finish` must be called with
+    pub fn start(&self, outputs: &[String]) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state
+            .outputs
+            .retain(|(output, _)| !outputs.contains(output));
+        state
+            .outputs
+            .extend(outputs.iter().map(|output| (output.clone(), id)));
+        id
+    }
+
+    /// Unmarks the outputs that are still running the transition `id`, returning them
+    pub fn finish(&self, outputs: &[String], id: u64) -> Vec<String> {
+        self.unmark(|output, output_id| output_id == id && outputs.contains(output))
+    }
+
+    /// Unmarks `outputs`, whatever transition they were running. Returns the ones that were
+    /// transitioning
+    pub fn stop(&self, outputs: &[String]) -> Vec<String> {
+        self.unmark(|output, _| outputs.contains(output))
+    }
+
+    fn unmark(&self, mut f: impl FnMut(&String, u64) -> bool) -> Vec<String> {
+        let mut state = self.state.lock().unwrap();
+        let mut unmarked = Vec::new();
+        state.outputs.retain(|(output, id)| {
+            if f(output, *id) {
+                unmarked.push(output.clone());
+                false
+            } else {
+                true
+            }
+        });
+        self.condvar.notify_all();
+        unmarked
+    }
+
+    pub fn contains(&self, output: &str) -> bool {
+        let state = self.state.lock().unwrap();
+        state.outputs.iter().any(|(o, _)| o == output)
+    }
+
+    /// Blocks until none of `outputs` is transitioning. If `outputs` is empty, waits for every
+    /// output
+    pub fn wait_for(&self, outputs: &[String]) {
+        let mut state = self.state.lock().unwrap();
+        while state
+            .outputs
+            .iter()
+            .any(|(output, _)| outputs.is_empty() || outputs.contains(output))
+        {
+            state = self.condvar.wait(state).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread, time::Duration};
+
+    #[test]
+    fn replaced_transitions_should_not_unmark_outputs() {
+        let transitions = OnGoingTransitions::new();
+        let outputs = vec!["DP-1".to_string()];
+        let old = transitions.start(&outputs);
+        let new = transitions.start(&outputs);
+        assert!(transitions.finish(&outputs, old).is_empty());
+        assert!(transitions.contains("DP-1"));
+        assert_eq!(transitions.finish(&outputs, new), outputs);
+        assert!(!transitions.contains("DP-1"));
+    }
+
+    #[test]
+    fn should_wait_for_the_requested_outputs() {
+        let transitions = Arc::new(OnGoingTransitions::new());
+        let dp1 = vec!["DP-1".to_string()];
+        let dp2 = vec!["DP-2".to_string()];
+        let id1 = transitions.start(&dp1);
+        let id2 = transitions.start(&dp2);
+
+        let waiter = {
+            let transitions = Arc::clone(&transitions);
+            let dp1 = dp1.clone();
+            thread::spawn(move || transitions.wait_for(&dp1))
+        };
+        transitions.finish(&dp2, id2);
+        thread::sleep(Duration::from_millis(10));
+        assert!(
+            !waiter.is_finished(),
+            "stopped waiting for the wrong output"
+        );
+        transitions.finish(&dp1, id1);
+        waiter.join().unwrap();
+        // nothing is transitioning, so this returns immediately
+        transitions.wait_for(&[]);
+    }
+}
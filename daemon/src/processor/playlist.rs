@@ -0,0 +1,350 @@
+use log::{debug, error, warn};
+use rand::seq::SliceRandom;
+
+use smithay_client_toolkit::reexports::calloop::channel::Sender;
+
+use std::{
+    path::PathBuf,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use utils::communication::{
+    self, ImageRequest, Img, PlaylistCommand, PlaylistOrder, Resize, Transition,
+};
+
+use super::ProcessedImg;
+
+/// The dimensions of the outputs the playlist is running on, grouped together so that we only
+/// resize the image once for every different dimension
+pub type OutputGroups = Vec<((u32, u32), Vec<String>)>;
+
+pub enum PlaylistMsg {
+    Command(PlaylistCommand),
+    /// Sent whenever outputs are reconfigured or removed from the playlist. Once this is empty,
+    /// the playlist stops
+    Outputs(OutputGroups),
+}
+
+pub struct Playlist {
+    /// Sent along with our images, so that those still on their way once we are stopped can be
+    /// told apart from the ones of the playlist that replaced us
+    id: u64,
+    sources: Vec<PathBuf>,
+    order: PlaylistOrder,
+    interval: Duration,
+    resize: Resize,
+    transition: Transition,
+    groups: OutputGroups,
+    /// The images of the current cycle, already in the order they will be displayed
+    queue: Vec<PathBuf>,
+    idx: usize,
+    /// The queue of the previous cycle, so that going back from the start of the queue shows the
+    /// image that was actually on screen before
+    previous: Vec<PathBuf>,
+    /// Once we went back to the previous cycle, the queue we left, to go forward to it again
+    upcoming: Option<Vec<PathBuf>>,
+}
+
+impl Playlist {
+    pub fn new(
+        id: u64,
+        request: communication::Playlist,
+        groups: OutputGroups,
+    ) -> Result<Self, String> {
+        let mut playlist = Self {
+            id,
+            sources: request.paths,
+            order: request.order,
+            interval: request.interval,
+            resize: request.resize,
+            transition: request.transition,
+            groups,
+            queue: Vec::new(),
+            idx: 0,
+            previous: Vec::new(),
+            upcoming: None,
+        };
+        playlist.make_queue();
+        if playlist.queue.is_empty() {
+            return Err("no images found in the playlist's paths".to_string());
+        }
+        Ok(playlist)
+    }
+
+    /// Collects all images from our sources, scanning directories again, so that images added or
+    /// removed since the last cycle are taken into account
+    fn make_queue(&mut self) {
+        self.previous = std::mem::take(&mut self.queue);
+        // when starting a new cycle, the image on screen is the last one of the previous cycle
+        let last = self.previous.last().cloned();
+        for source in &self.sources {
+            if source.is_dir() {
+                let entries = match source.read_dir() {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        error!("failed to read playlist directory {source:?}: {e}");
+                        continue;
+                    }
+                };
+                let mut imgs: Vec<PathBuf> = entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_file() && image::ImageFormat::from_path(path).is_ok())
+                    .collect();
+                imgs.sort();
+                self.queue.append(&mut imgs);
+            } else {
+                self.queue.push(source.clone());
+            }
+        }
+
+        if self.order == PlaylistOrder::Shuffle {
+            self.queue.shuffle(&mut rand::thread_rng());
+            // avoid showing the same image twice in a row when starting a new cycle
+            if self.queue.len() > 1 && self.queue.first() == last.as_ref() {
+                let len = self.queue.len();
+                self.queue.swap(0, len - 1);
+            }
+        }
+        self.idx = 0;
+    }
+
+    fn next(&mut self) {
+        self.idx += 1;
+        if self.idx >= self.queue.len() {
+            match self.upcoming.take() {
+                Some(upcoming) => {
+                    self.previous = std::mem::replace(&mut self.queue, upcoming);
+                    self.idx = 0;
+                }
+                None => self.make_queue(),
+            }
+        }
+    }
+
+    fn prev(&mut self) {
+        match self.idx.checked_sub(1) {
+            Some(idx) => self.idx = idx,
+            None if !self.previous.is_empty() => {
+                let previous = std::mem::take(&mut self.previous);
+                self.upcoming = Some(std::mem::replace(&mut self.queue, previous));
+                self.idx = self.queue.len() - 1;
+            }
+            // we only remember one cycle back
+            None => self.idx = self.queue.len().saturating_sub(1),
+        }
+    }
+
+    /// Loads the current image and resizes it for all our outputs. If it fails, we try the next
+    /// ones, until we have gone through the whole queue. Skipping past its end makes the queue
+    /// again, so it may change size, or even become empty, along the way
+    fn load_current(&mut self) -> Option<ImageRequest> {
+        let mut attempts = 0;
+        while attempts < self.queue.len() {
+            let path = self.queue.get(self.idx)?.clone();
+            match self.load(&path) {
+                Ok(imgs) => return Some((self.transition.clone(), imgs)),
+                Err(e) => error!("skipping {path:?} in playlist: {e}"),
+            }
+            attempts += 1;
+            self.next();
+        }
+        None
+    }
+
+    fn load(&self, path: &PathBuf) -> Result<Vec<(Img, Vec<String>)>, String> {
+        let img = match image::open(path) {
+            Ok(img) => img.into_rgba8(),
+            Err(e) => return Err(format!("failed to decode image: {e}")),
+        };
+
+        let mut imgs = Vec::with_capacity(self.groups.len());
+        for (dim, outputs) in &self.groups {
+            imgs.push((
+                Img {
+                    img: utils::resize::resize(img.clone(), *dim, &self.resize)?,
+                    path: path.clone(),
+                    span: None,
+                },
+                outputs.clone(),
+            ));
+        }
+        Ok(imgs)
+    }
+
+    /// Displays the current image, returning whether the playlist should keep running
+    fn show(&mut self, sender: &Sender<ProcessedImg>) -> bool {
+        match self.load_current() {
+            Some(request) => sender.send((Some(self.id), request)).is_ok(),
+            None => {
+                error!("none of the playlist's images could be loaded. Stopping playlist...");
+                false
+            }
+        }
+    }
+
+    pub fn run(mut self, sender: Sender<ProcessedImg>, msg_recv: mpsc::Receiver<PlaylistMsg>) {
+        if !self.show(&sender) {
+            return;
+        }
+        let mut deadline = Instant::now() + self.interval;
+        // When paused, how long was left until the next image
+        let mut paused: Option<Duration> = None;
+
+        loop {
+            let msg = match paused {
+                Some(_) => msg_recv
+                    .recv()
+                    .map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+                None => msg_recv.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            };
+
+            match msg {
+                Err(mpsc::RecvTimeoutError::Timeout)
+                | Ok(PlaylistMsg::Command(PlaylistCommand::Next)) => {
+                    self.next();
+                    if !self.show(&sender) {
+                        return;
+                    }
+                    deadline = Instant::now() + self.interval;
+                    if paused.is_some() {
+                        paused = Some(self.interval);
+                    }
+                }
+                Ok(PlaylistMsg::Command(PlaylistCommand::Prev)) => {
+                    self.prev();
+                    if !self.show(&sender) {
+                        return;
+                    }
+                    deadline = Instant::now() + self.interval;
+                    if paused.is_some() {
+                        paused = Some(self.interval);
+                    }
+                }
+                Ok(PlaylistMsg::Command(PlaylistCommand::Pause)) => {
+                    if paused.is_none() {
+                        paused = Some(deadline.saturating_duration_since(Instant::now()));
+                    }
+                }
+                Ok(PlaylistMsg::Command(PlaylistCommand::Resume)) => {
+                    if let Some(remaining) = paused.take() {
+                        deadline = Instant::now() + remaining;
+                    }
+                }
+                Ok(PlaylistMsg::Outputs(groups)) => {
+                    if groups.is_empty() {
+                        debug!("Playlist has no more outputs");
+                        return;
+                    }
+                    self.groups = groups;
+                }
+                Ok(PlaylistMsg::Command(PlaylistCommand::Stop)) => {
+                    debug!("Playlist was stopped");
+                    return;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    warn!("Playlist lost connection to the processor");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_playlist(order: PlaylistOrder) -> Playlist {
+        let paths = (0..10)
+            .map(|i| PathBuf::from(format!("img{i}.png")))
+            .collect();
+        playlist_of(paths, order)
+    }
+
+    fn playlist_of(paths: Vec<PathBuf>, order: PlaylistOrder) -> Playlist {
+        let request = communication::Playlist {
+            paths,
+            interval: Duration::from_secs(1),
+            order,
+            resize: Resize::default(),
+            transition: Transition::default(),
+            outputs: vec!["dummy".to_string()],
+        };
+        Playlist::new(0, request, vec![((1, 1), vec!["dummy".to_string()])]).unwrap()
+    }
+
+    #[test]
+    fn shuffle_should_show_every_image_once_per_cycle() {
+        let mut playlist = test_playlist(PlaylistOrder::Shuffle);
+        let mut last = None;
+        for _ in 0..10 {
+            let mut seen = Vec::new();
+            for _ in 0..10 {
+                let current = playlist.queue[playlist.idx].clone();
+                assert!(
+                    !seen.contains(&current),
+                    "{current:?} was repeated in a cycle"
+                );
+                assert_ne!(
+                    Some(&current),
+                    last.as_ref(),
+                    "{current:?} was shown twice in a row"
+                );
+                seen.push(current.clone());
+                last = Some(current);
+                playlist.next();
+            }
+        }
+    }
+
+    #[test]
+    fn prev_should_go_back_to_previous_image() {
+        let mut playlist = test_playlist(PlaylistOrder::Sequential);
+        assert_eq!(playlist.queue[playlist.idx], PathBuf::from("img0.png"));
+        playlist.prev();
+        assert_eq!(playlist.queue[playlist.idx], PathBuf::from("img9.png"));
+        playlist.next();
+        playlist.next();
+        playlist.prev();
+        assert_eq!(playlist.queue[playlist.idx], PathBuf::from("img0.png"));
+    }
+
+    #[test]
+    fn shuffle_prev_should_go_back_to_the_previous_cycle() {
+        let mut playlist = test_playlist(PlaylistOrder::Shuffle);
+        for _ in 0..9 {
+            playlist.next();
+        }
+        let last_of_cycle = playlist.queue[playlist.idx].clone();
+        playlist.next();
+        let first_of_cycle = playlist.queue[playlist.idx].clone();
+
+        playlist.prev();
+        assert_eq!(playlist.queue[playlist.idx], last_of_cycle);
+        playlist.next();
+        assert_eq!(playlist.queue[playlist.idx], first_of_cycle);
+    }
+
+    #[test]
+    fn should_stop_when_the_images_are_gone() {
+        let dir = std::env::temp_dir().join(format!("swww-playlist-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for i in 0..3 {
+            image::RgbaImage::new(1, 1)
+                .save(dir.join(format!("img{i}.png")))
+                .unwrap();
+        }
+
+        let mut playlist = playlist_of(vec![dir.clone()], PlaylistOrder::Sequential);
+        assert!(playlist.load_current().is_some());
+        playlist.next();
+        for i in 0..3 {
+            std::fs::remove_file(dir.join(format!("img{i}.png"))).unwrap();
+        }
+        assert!(playlist.load_current().is_none());
+
+        std::fs::remove_dir(dir).unwrap();
+    }
+}
@@ -0,0 +1,79 @@
+use std::path::{Path, PathBuf};
+
+use utils::communication::{BgInfo, ImageRequest, Img, Span, Transition};
+
+/// The name, position, logical dimensions and scale factor of every output in a span
+pub type Layout = Vec<(String, (i32, i32), (u32, u32), i32)>;
+
+/// An image that is currently spanned across several outputs
+pub struct Spanned {
+    pub span: Span,
+    pub path: PathBuf,
+    pub transition: Transition,
+    /// The layout we last spanned the image across
+    pub layout: Layout,
+}
+
+impl Spanned {
+    pub fn new(span: Span, path: PathBuf, transition: Transition, infos: &[BgInfo]) -> Self {
+        let layout = layout(&span.outputs, infos);
+        Self {
+            span,
+            path,
+            transition,
+            layout,
+        }
+    }
+}
+
+/// Outputs that haven't been configured yet are left out
+pub fn layout(outputs: &[String], infos: &[BgInfo]) -> Layout {
+    infos
+        .iter()
+        .filter(|info| outputs.contains(&info.name) && info.dim.0 != 0 && info.dim.1 != 0)
+        .map(|info| {
+            (
+                info.name.clone(),
+                info.position,
+                info.dim,
+                info.scale_factor,
+            )
+        })
+        .collect()
+}
+
+/// Opens the image again and spans it across the outputs in `layout`
+pub fn respan(
+    path: &Path,
+    span: &Span,
+    transition: &Transition,
+    layout: &Layout,
+    infos: &[BgInfo],
+) -> Result<ImageRequest, String> {
+    let img = match image::open(path) {
+        Ok(img) => img.into_rgba8(),
+        Err(e) => return Err(format!("failed to decode image: {e}")),
+    };
+
+    let outputs: Vec<&BgInfo> = infos
+        .iter()
+        .filter(|info| layout.iter().any(|(name, ..)| name == &info.name))
+        .collect();
+    let imgs = utils::resize::span(img, &outputs, &span.resize)?;
+    Ok((
+        transition.clone(),
+        imgs.into_iter()
+            .zip(outputs)
+            .map(|(img, info)| {
+                (
+                    Img {
+                        img,
+                        path: path.to_owned(),
+                        span: Some(span.clone()),
+                    },
+                    vec![info.name.clone()],
+                )
+            })
+            .collect(),
+    ))
+}
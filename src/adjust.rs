@@ -0,0 +1,151 @@
+//! Adjustments applied to the images after resizing them. See `cli::AdjustOpts`
+
+use image::RgbaImage;
+
+use crate::cli::AdjustOpts;
+
+/// Whether any of the options would change the image
+pub fn has_adjustments(opts: &AdjustOpts) -> bool {
+    opts.blur > 0.0
+        || opts.brightness != 1.0
+        || opts.contrast != 1.0
+        || opts.saturation != 1.0
+        || opts.grayscale
+        || opts.tint.is_some()
+}
+
+/// Applies every adjustment in `opts` to `img`, an already resized BGRA image of dimensions `dim`
+pub fn adjust(img: Vec<u8>, dim: (u32, u32), opts: &AdjustOpts) -> Result<Vec<u8>, String> {
+    if !has_adjustments(opts) {
+        return Ok(img);
+    }
+
+    let mut img = if opts.blur > 0.0 {
+        let buffer = match RgbaImage::from_raw(dim.0, dim.1, img) {
+            Some(buffer) => buffer,
+            None => return Err("image is smaller than its dimensions".to_string()),
+        };
+        // the blur treats every channel the same, so it doesn't matter that we are in BGRA
+        image::imageops::blur(&buffer, opts.blur).into_raw()
+    } else {
+        img
+    };
+
+    let saturation = if opts.grayscale { 0.0 } else { opts.saturation };
+    if opts.brightness == 1.0 && opts.contrast == 1.0 && saturation == 1.0 && opts.tint.is_none() {
+        // only blurred
+        return Ok(img);
+    }
+
+    let tint = opts.tint.map(|[r, g, b]| {
+        [
+            f32::from(b) / 255.0,
+            f32::from(g) / 255.0,
+            f32::from(r) / 255.0,
+        ]
+    });
+    for pixel in img.chunks_exact_mut(4) {
+        let mut bgr = [
+            f32::from(pixel[0]),
+            f32::from(pixel[1]),
+            f32::from(pixel[2]),
+        ];
+        for c in bgr.iter_mut() {
+            *c = (*c * opts.brightness - 127.5) * opts.contrast + 127.5;
+        }
+
+        let luma = 0.0722 * bgr[0] + 0.7152 * bgr[1] + 0.2126 * bgr[2];
+        for c in bgr.iter_mut() {
+            *c = luma + (*c - luma) * saturation;
+        }
+
+        if let Some(tint) = tint {
+            for (c, t) in bgr.iter_mut().zip(tint) {
+                *c *= t;
+            }
+        }
+
+        for (p, c) in pixel.iter_mut().zip(bgr) {
+            *p = c.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    Ok(img)
+}
+
+/// The options that leave images untouched, for tests to start from
+#[cfg(test)]
+pub fn no_adjustments() -> AdjustOpts {
+    AdjustOpts {
+        blur: 0.0,
+        brightness: 1.0,
+        contrast: 1.0,
+        saturation: 1.0,
+        grayscale: false,
+        tint: None,
+    }
+}
+
+/// Like `adjust`, but for an RGBA image that was not resized yet. Spanned images are adjusted as a
+/// whole before being cut into parts, so that blurring them leaves no seams between the outputs
+pub fn adjust_rgba(img: RgbaImage, opts: &AdjustOpts) -> Result<RgbaImage, String> {
+    let dim = img.dimensions();
+    let mut img = img.into_raw();
+    swap_red_and_blue(&mut img);
+    let mut img = adjust(img, dim, opts)?;
+    swap_red_and_blue(&mut img);
+    match RgbaImage::from_raw(dim.0, dim.1, img) {
+        Some(img) => Ok(img),
+        None => Err("image is smaller than its dimensions".to_string()),
+    }
+}
+
+fn swap_red_and_blue(img: &mut [u8]) {
+    for pixel in img.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_not_change_image_without_adjustments() {
+        let img = vec![10, 100, 200, 255, 0, 255, 30, 255];
+        assert_eq!(adjust(img.clone(), (2, 1), &no_adjustments()).unwrap(), img);
+    }
+
+    #[test]
+    fn should_adjust_colors() {
+        let img = vec![10, 100, 200, 255];
+
+        let mut darker = no_adjustments();
+        darker.brightness = 0.5;
+        assert_eq!(
+            adjust(img.clone(), (1, 1), &darker).unwrap(),
+            [5, 50, 100, 255]
+        );
+
+        let mut gray = no_adjustments();
+        gray.grayscale = true;
+        let gray = adjust(img.clone(), (1, 1), &gray).unwrap();
+        assert!(
+            gray[0] == gray[1] && gray[1] == gray[2],
+            "not gray: {gray:?}"
+        );
+
+        // the tint is in RRGGBB, but the image is in BGRA
+        let mut red = no_adjustments();
+        red.tint = Some([255, 0, 0]);
+        assert_eq!(adjust(img, (1, 1), &red).unwrap(), [0, 0, 200, 255]);
+    }
+
+    #[test]
+    fn should_adjust_rgba_images() {
+        let img = RgbaImage::from_raw(1, 1, vec![200, 100, 10, 255]).unwrap();
+        let mut red = no_adjustments();
+        red.tint = Some([255, 0, 0]);
+        assert_eq!(adjust_rgba(img, &red).unwrap().into_raw(), [200, 0, 0, 255]);
+    }
+}
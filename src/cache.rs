@@ -0,0 +1,377 @@
+//! The on-disk cache of processed images.
+//!
+//! Resizing and adjusting images, and above all compressing every frame of an animation, can take
+//! several seconds, so `swww img` keeps the results at `<cache path>/processed`. Every entry is
+//! named after a hash of the source file's contents and modification time, the dimensions it was
+//! made for and every option that changes the result, so a changed file or option simply misses.
+//!
+//! An entry holds the source's path and the dimensions, so that `swww cache list` can show them,
+//! followed by the processed image (for `.img` files) or the compressed frames (for `.anim` files).
+//! Reading an entry updates its modification time, which is what `prune` uses to find the least
+//! recently used ones.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use utils::{
+    communication::{get_cache_path, Resize},
+    comp_decomp::BitPack,
+};
+
+use crate::cli::AdjustOpts;
+
+const IMG_EXT: &str = "img";
+const ANIMATION_EXT: &str = "anim";
+
+/// Identifies the current contents of a source image
+pub struct Key {
+    source: PathBuf,
+    hash: u64,
+    dir: PathBuf,
+}
+
+impl Key {
+    /// Hashes the image at `path`. Images we can't cache, like the ones read from stdin, give
+    /// `None`
+    pub fn new(path: &Path) -> Option<Self> {
+        if let Some("-") = path.to_str() {
+            return None;
+        }
+        let dir = match processed_dir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                eprintln!("WARNING: not caching processed images: {e}");
+                return None;
+            }
+        };
+        // if this fails, so will reading the image, which gives a better error message
+        Self::in_dir(path, dir).ok()
+    }
+
+    fn in_dir(path: &Path, dir: PathBuf) -> Result<Self, String> {
+        let source = match path.canonicalize() {
+            Ok(source) => source,
+            Err(e) => return Err(format!("failed to canonicalize {path:?}: {e}")),
+        };
+        let modified = match std::fs::metadata(&source).and_then(|m| m.modified()) {
+            Ok(modified) => modified
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default(),
+            Err(e) => return Err(format!("failed to read the metadata of {source:?}: {e}")),
+        };
+        let contents = match std::fs::read(&source) {
+            Ok(contents) => contents,
+            Err(e) => return Err(format!("failed to read {source:?}: {e}")),
+        };
+
+        let mut hasher = DefaultHasher::new();
+        // the results may change between versions
+        hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+        source.hash(&mut hasher);
+        hasher.write(&contents);
+        hasher.write_u128(modified.as_nanos());
+        Ok(Self {
+            source,
+            hash: hasher.finish(),
+            dir,
+        })
+    }
+
+    /// The entry for this image once processed with the given options
+    pub fn entry(&self, dim: (u32, u32), resize: &Resize, adjust: &AdjustOpts) -> Entry {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u64(self.hash);
+        hasher.write_u32(dim.0);
+        hasher.write_u32(dim.1);
+        // Resize has floats in it, so it can't be hashed directly
+        match bincode::serialize(resize) {
+            Ok(resize) => hasher.write(&resize),
+            Err(e) => unreachable!("failed to serialize resize options: {e}"),
+        }
+        hasher.write_u32(adjust.blur.to_bits());
+        hasher.write_u32(adjust.brightness.to_bits());
+        hasher.write_u32(adjust.contrast.to_bits());
+        hasher.write_u32(adjust.saturation.to_bits());
+        hasher.write_u8(adjust.grayscale.into());
+        match adjust.tint {
+            Some(tint) => hasher.write(&tint),
+            None => hasher.write(&[0, 0, 0, 0]),
+        }
+
+        Entry {
+            path: self.dir.join(format!("{:016x}", hasher.finish())),
+            source: self.source.clone(),
+            dim,
+        }
+    }
+}
+
+/// A processed image or animation, which may or may not be in the cache yet
+#[derive(Clone)]
+pub struct Entry {
+    /// Path to the entry, without the extension
+    path: PathBuf,
+    source: PathBuf,
+    dim: (u32, u32),
+}
+
+impl Entry {
+    pub fn load_img(&self) -> Option<Vec<u8>> {
+        let mut reader = self.open(IMG_EXT)?;
+        bincode::deserialize_from(&mut reader).ok()
+    }
+
+    pub fn load_animation(&self) -> Option<Vec<(BitPack, Duration)>> {
+        let mut reader = self.open(ANIMATION_EXT)?;
+        bincode::deserialize_from(&mut reader).ok()
+    }
+
+    pub fn store_img(&self, img: &[u8]) {
+        self.store(IMG_EXT, |writer| bincode::serialize_into(writer, img));
+    }
+
+    pub fn store_animation(&self, frames: &[(BitPack, Duration)]) {
+        self.store(ANIMATION_EXT, |writer| {
+            bincode::serialize_into(writer, frames)
+        });
+    }
+
+    /// Opens the entry, positioning the reader right after its header
+    fn open(&self, ext: &str) -> Option<BufReader<File>> {
+        let path = self.path.with_extension(ext);
+        let file = File::options().write(true).read(true).open(path).ok()?;
+        // mark it as recently used, for `prune`
+        if let Err(e) = file.set_modified(SystemTime::now()) {
+            eprintln!("WARNING: failed to update the cache's last use time: {e}");
+        }
+
+        let mut reader = BufReader::new(file);
+        let (source, dim) = read_header(&mut reader)?;
+        if source == self.source && dim == self.dim {
+            Some(reader)
+        } else {
+            None
+        }
+    }
+
+    /// Failing to cache something isn't worth failing the whole command over, so we only warn
+    fn store<F>(&self, ext: &str, write_payload: F)
+    where
+        F: FnOnce(&mut BufWriter<File>) -> bincode::Result<()>,
+    {
+        let path = self.path.with_extension(ext);
+        // write to a temporary file first, so that no one ever reads a half written entry
+        let tmp = self
+            .path
+            .with_extension(format!("{ext}.{}.tmp", std::process::id()));
+        let result = File::create(&tmp)
+            .map_err(|e| e.to_string())
+            .and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                bincode::serialize_into(&mut writer, &self.source)
+                    .and_then(|()| bincode::serialize_into(&mut writer, &self.dim))
+                    .and_then(|()| write_payload(&mut writer))
+                    .map_err(|e| e.to_string())?;
+                writer.into_inner().map_err(|e| e.to_string())?;
+                std::fs::rename(&tmp, &path).map_err(|e| e.to_string())
+            });
+
+        if let Err(e) = result {
+            eprintln!("WARNING: failed to cache {:?}: {e}", self.source);
+            let _ = std::fs::remove_file(&tmp);
+        }
+    }
+}
+
+fn read_header(reader: &mut BufReader<File>) -> Option<(PathBuf, (u32, u32))> {
+    let source = bincode::deserialize_from(&mut *reader).ok()?;
+    let dim = bincode::deserialize_from(&mut *reader).ok()?;
+    Some((source, dim))
+}
+
+/// An entry in the cache, as shown by `swww cache list`
+pub struct Listing {
+    path: PathBuf,
+    pub source: PathBuf,
+    pub dim: (u32, u32),
+    pub animated: bool,
+    pub size: u64,
+    pub last_used: SystemTime,
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>10}  {:>9}  {:<9}  {}",
+            format_size(self.size),
+            format!("{}x{}", self.dim.0, self.dim.1),
+            if self.animated { "animation" } else { "image" },
+            self.source.display()
+        )
+    }
+}
+
+/// Every entry in the cache, from the most to the least recently used
+pub fn list() -> Result<Vec<Listing>, String> {
+    list_dir(&processed_dir()?)
+}
+
+/// Deletes every entry in the cache
+pub fn clear() -> Result<(), String> {
+    let dir = processed_dir()?;
+    match std::fs::remove_dir_all(&dir) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("failed to delete {}: {e}", dir.display())),
+    }
+}
+
+/// Deletes the least recently used entries until the cache takes at most `max_size` bytes.
+///
+/// Returns how many entries were deleted.
+pub fn prune(max_size: u64) -> Result<usize, String> {
+    prune_dir(&processed_dir()?, max_size)
+}
+
+fn prune_dir(dir: &Path, max_size: u64) -> Result<usize, String> {
+    let mut size = 0;
+    let mut deleted = 0;
+    for listing in list_dir(dir)? {
+        size += listing.size;
+        if size <= max_size {
+            continue;
+        }
+        match std::fs::remove_file(&listing.path) {
+            Ok(()) => deleted += 1,
+            Err(e) => return Err(format!("failed to delete {:?}: {e}", listing.path)),
+        }
+    }
+    Ok(deleted)
+}
+
+fn list_dir(dir: &Path) -> Result<Vec<Listing>, String> {
+    let read_dir = match std::fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("failed to read {}: {e}", dir.display())),
+    };
+
+    let mut listings = Vec::new();
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        let animated = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(IMG_EXT) => false,
+            Some(ANIMATION_EXT) => true,
+            // temporary files
+            _ => continue,
+        };
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(e) => return Err(format!("failed to read the metadata of {path:?}: {e}")),
+        };
+        let mut reader = match File::open(&path) {
+            Ok(file) => BufReader::new(file),
+            Err(e) => return Err(format!("failed to open {path:?}: {e}")),
+        };
+        let (source, dim) = read_header(&mut reader).unwrap_or_default();
+        listings.push(Listing {
+            path,
+            source,
+            dim,
+            animated,
+            size: metadata.len(),
+            last_used: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        });
+    }
+    listings.sort_by_key(|listing| std::cmp::Reverse(listing.last_used));
+    Ok(listings)
+}
+
+fn processed_dir() -> Result<PathBuf, String> {
+    let dir = get_cache_path()?.join("processed");
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        return Err(format!("failed to create {}: {e}", dir.display()));
+    }
+    Ok(dir)
+}
+
+/// Formats `size` in bytes with binary prefixes, like `1.5MiB`
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{size}B");
+    }
+    let mut size = size as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1}{}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adjust::no_adjustments as adjust_opts;
+    use utils::communication::ResizeStrategy;
+
+    fn resize_opts(strategy: ResizeStrategy) -> Resize {
+        Resize {
+            strategy,
+            ..Resize::default()
+        }
+    }
+
+    #[test]
+    fn should_cache_processed_images() {
+        let dir = std::env::temp_dir().join(format!("swww-processed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source.png");
+        std::fs::write(&source, "not really a png").unwrap();
+
+        let key = Key::in_dir(&source, dir.clone()).unwrap();
+        let crop = key.entry((2, 1), &resize_opts(ResizeStrategy::Crop), &adjust_opts());
+        assert_eq!(crop.load_img(), None);
+        crop.store_img(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(crop.load_img(), Some(vec![1, 2, 3, 4, 5, 6, 7, 8]));
+
+        // any change to the options or the source misses
+        let fit = key.entry((2, 1), &resize_opts(ResizeStrategy::Fit), &adjust_opts());
+        assert_eq!(fit.load_img(), None);
+        let mut darker = adjust_opts();
+        darker.brightness = 0.5;
+        let darker = key.entry((2, 1), &resize_opts(ResizeStrategy::Crop), &darker);
+        assert_eq!(darker.load_img(), None);
+        std::fs::write(&source, "changed").unwrap();
+        let key = Key::in_dir(&source, dir.clone()).unwrap();
+        let changed = key.entry((2, 1), &resize_opts(ResizeStrategy::Crop), &adjust_opts());
+        assert_eq!(changed.load_img(), None);
+
+        // pruning deletes the least recently used entries first
+        fit.store_img(&[0; 8]);
+        File::options()
+            .write(true)
+            .open(fit.path.with_extension(IMG_EXT))
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        let listings = list_dir(&dir).unwrap();
+        assert_eq!(listings.len(), 2);
+        assert_eq!(listings[0].source, source.canonicalize().unwrap());
+        assert_eq!(listings[0].dim, (2, 1));
+        assert_eq!(prune_dir(&dir, listings[0].size).unwrap(), 1);
+        assert_eq!(fit.load_img(), None);
+        assert!(crop.load_img().is_some());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
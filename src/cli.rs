@@ -1,41 +1,139 @@
 /// Note: this file only has basic declarations and some definitions in order to be possible to
 /// import it in the build script, to automate shell completion
-use clap::{Parser, ValueEnum};
-use std::path::PathBuf;
-
-fn from_hex(hex: &str) -> Result<[u8; 3], String> {
-    let chars = hex
-        .chars()
-        .filter(|&c| c.is_ascii_alphanumeric())
-        .map(|c| c.to_ascii_uppercase() as u8);
-
-    if chars.clone().count() != 6 {
-        return Err(format!(
-            "expected 6 characters, found {}",
-            chars.clone().count()
-        ));
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
+
+const COLOR_FORMATS: &str =
+    "expected #rrggbb, #rgb, rrggbbaa, rgb(r, g, b), hsl(h, s%, l%) or a CSS color name";
+
+/// Parses a color as `#rrggbb`, `#rgb` (with or without the `#`, and optionally followed by an
+/// alpha channel), `rgb(...)`, `rgba(...)`, `hsl(...)`, `hsla(...)` or a CSS color name. Wallpapers
+/// are always opaque, so the alpha channel is accepted but ignored
+fn parse_color(raw: &str) -> Result<[u8; 3], String> {
+    let color = raw.trim().to_ascii_lowercase();
+    if let Ok(i) = NAMED_COLORS.binary_search_by_key(&color.as_str(), |(name, _)| name) {
+        Ok(NAMED_COLORS[i].1)
+    } else if let Some(args) = functional_color(&color, "rgb") {
+        parse_rgb(args)
+    } else if let Some(args) = functional_color(&color, "hsl") {
+        parse_hsl(args)
+    } else {
+        parse_hex(color.strip_prefix('#').unwrap_or(&color))
     }
+}
 
-    let mut color = [0, 0, 0];
+/// A comma separated list of colors. It is a type of its own so that clap doesn't split the list
+/// by itself, since `parse_colors` must skip the commas inside `rgb(...)`
+#[derive(Clone)]
+pub struct Colors(pub Vec<[u8; 3]>);
 
-    for (i, c) in chars.enumerate() {
+/// Parses a comma separated list of colors. Commas inside parentheses, like in `rgb(1, 2, 3)`,
+/// belong to the color
+fn parse_colors(raw: &str) -> Result<Colors, String> {
+    let mut colors = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in raw.char_indices() {
         match c {
-            b'A'..=b'F' => color[i / 2] += c - b'A' + 10,
-            b'0'..=b'9' => color[i / 2] += c - b'0',
-            _ => {
-                return Err(format!(
-                    "expected [0-9], [a-f], or [A-F], found '{}'",
-                    char::from(c)
-                ))
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                colors.push(parse_list_color(&raw[start..i])?);
+                start = i + 1;
             }
+            _ => (),
         }
-        if i % 2 == 0 {
-            color[i / 2] *= 16;
+    }
+    colors.push(parse_list_color(&raw[start..])?);
+    Ok(Colors(colors))
+}
+
+fn parse_list_color(raw: &str) -> Result<[u8; 3], String> {
+    parse_color(raw).map_err(|e| format!("invalid color '{raw}': {e}"))
+}
+
+fn parse_hex(hex: &str) -> Result<[u8; 3], String> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(COLOR_FORMATS.to_string());
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..=i], 16).unwrap_or(0);
+    match hex.len() {
+        3 | 4 => Ok([digit(0) * 17, digit(1) * 17, digit(2) * 17]),
+        6 | 8 => Ok([
+            digit(0) * 16 + digit(1),
+            digit(2) * 16 + digit(3),
+            digit(4) * 16 + digit(5),
+        ]),
+        len => Err(format!(
+            "expected 3, 4, 6 or 8 hex digits, found {len} ({COLOR_FORMATS})"
+        )),
+    }
+}
+
+/// Returns the arguments of `name(...)` or `namea(...)`
+fn functional_color<'a>(color: &'a str, name: &str) -> Option<&'a str> {
+    let args = color.strip_prefix(name)?;
+    let args = args.strip_prefix('a').unwrap_or(args);
+    args.trim_start().strip_prefix('(')?.strip_suffix(')')
+}
+
+/// Splits the arguments of `rgb(...)` or `hsl(...)`, which may be separated by commas or spaces,
+/// dropping the alpha channel
+fn color_args(args: &str) -> Result<[&str; 3], String> {
+    let args = args.split('/').next().unwrap_or(args);
+    let args: Vec<&str> = args
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .collect();
+    match args[..] {
+        [a, b, c] | [a, b, c, _] => Ok([a, b, c]),
+        _ => Err(format!("expected 3 values, found {}", args.len())),
+    }
+}
+
+fn parse_rgb(args: &str) -> Result<[u8; 3], String> {
+    let mut color = [0; 3];
+    for (channel, arg) in color.iter_mut().zip(color_args(args)?) {
+        let value = match arg.strip_suffix('%') {
+            Some(percent) => parse_number(percent)? * 255.0 / 100.0,
+            None => parse_number(arg)?,
+        };
+        if !(0.0..=255.0).contains(&value) {
+            return Err(format!("rgb values must be between 0 and 255, found {arg}"));
         }
+        *channel = value.round() as u8;
     }
     Ok(color)
 }
 
+fn parse_hsl(args: &str) -> Result<[u8; 3], String> {
+    let [h, s, l] = color_args(args)?;
+    let h = parse_number(h.strip_suffix("deg").unwrap_or(h))?.rem_euclid(360.0);
+    let percent = |arg: &str| match parse_number(arg.strip_suffix('%').unwrap_or(arg)) {
+        Ok(value) if (0.0..=100.0).contains(&value) => Ok(value / 100.0),
+        Ok(_) => Err(format!(
+            "saturation and lightness must be between 0% and 100%, found {arg}"
+        )),
+        Err(e) => Err(e),
+    };
+    let (s, l) = (percent(s)?, percent(l)?);
+
+    // https://www.w3.org/TR/css-color-4/#hsl-to-rgb
+    let channel = |n: f32| {
+        let k = (n + h / 30.0) % 12.0;
+        let a = s * l.min(1.0 - l);
+        let value = l - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0);
+        (value * 255.0).round() as u8
+    };
+    Ok([channel(0.0), channel(8.0), channel(4.0)])
+}
+
+fn parse_number(raw: &str) -> Result<f32, String> {
+    match raw.parse::<f32>() {
+        Ok(n) if n.is_finite() => Ok(n),
+        _ => Err(format!("expected a number, found '{raw}'")),
+    }
+}
+
 #[derive(Clone)]
 pub enum Filter {
     Nearest,
@@ -65,6 +163,7 @@ impl std::str::FromStr for Filter {
 #[derive(Clone)]
 pub enum TransitionType {
     Simple,
+    Fade,
     Left,
     Right,
     Top,
@@ -84,6 +183,7 @@ impl std::str::FromStr for TransitionType {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "simple" => Ok(Self::Simple),
+            "fade" => Ok(Self::Fade),
             "left" => Ok(Self::Left),
             "right" => Ok(Self::Right),
             "top" => Ok(Self::Top),
@@ -96,7 +196,7 @@ impl std::str::FromStr for TransitionType {
             "wave" => Ok(Self::Wave),
             "random" => Ok(Self::Random),
             _ => Err("unrecognized transition type.\nValid transitions are:\n\
-                     \tsimple | left | right | top | bottom | wipe | grow | center | outer | random | wave\n\
+                     \tsimple | fade | left | right | top | bottom | wipe | grow | center | outer | random | wave\n\
                      see swww img --help for more details"),
         }
     }
@@ -131,21 +231,42 @@ impl CliPosition {
 ///
 ///Note `swww` will only work in a compositor that implements the layer-shell protocol. Typically,
 ///wlr-roots based compositors.
+pub struct Cli {
+    /// Which daemon to talk to
+    ///
+    /// Every namespace has its own daemon, socket and cache, so that several daemons can run at
+    /// once, for example one per Wayland display. Defaults to the name of the Wayland display, as
+    /// in $WAYLAND_DISPLAY.
+    #[arg(long, global = true, env = "SWWW_NAMESPACE")]
+    pub namespace: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Swww,
+}
+
+#[derive(Subcommand)]
 pub enum Swww {
     ///Fills the specified outputs with the given color.
     ///
     ///Defaults to filling all outputs with black.
     Clear(Clear),
 
-    /// Sends an image (or animated gif) for the daemon to display.
+    /// Sends an image (or animated gif, png or webp) for the daemon to display.
     ///
     /// Use `-` to read from stdin
     Img(Img),
 
+    /// Cycles through a list of images, changing the wallpaper at a regular interval.
+    ///
+    /// The daemon keeps track of the schedule, so the playlist keeps running after this command
+    /// exits.
+    #[command(subcommand)]
+    Playlist(Playlist),
+
     /// Initializes the daemon.
     ///
-    /// Exits if there is already a daemon running. We check that by seeing if
-    /// $XDG_RUNTIME_DIR/swww.socket exists.
+    /// Exits if there is already a daemon running in the same namespace. We check that by trying
+    /// to talk to it through $XDG_RUNTIME_DIR/swww-<NAMESPACE>.socket.
     Init {
         ///Don't fork the daemon. This will keep it running in the current terminal.
         ///
@@ -158,26 +279,140 @@ pub enum Swww {
     ///Kills the daemon
     Kill,
 
+    /// Displays the cached wallpaper again.
+    ///
+    /// Every output's current wallpaper is cached, so this is useful after `swww clear`, or after
+    /// the daemon was restarted.
+    Restore(Restore),
+
+    /// Goes back to the previous wallpaper.
+    ///
+    /// Every output remembers the last wallpapers it displayed. Outputs that have no previous
+    /// wallpaper are left alone.
+    Back(Restore),
+
+    /// Goes forward to the next wallpaper, undoing `swww back`.
+    Forward(Restore),
+
+    /// Manages the cache of processed images.
+    ///
+    /// `swww img` caches every image after resizing it, and every animation after compressing its
+    /// frames, so that displaying them again is much faster. This does not need the daemon.
+    #[command(subcommand)]
+    Cache(Cache),
+
     ///Asks the daemon to print output information (names and dimensions).
     ///
     ///You may use this to find out valid values for the <swww-img --outputs> option. If you want
     ///more detailed information about your outputs, I would recommend trying wlr-randr.
-    Query,
+    Query {
+        /// Print the information as a JSON array, with one object per output
+        #[arg(long)]
+        json: bool,
+
+        /// Print one line per output, following the given format string
+        ///
+        /// The following placeholders are replaced: {name}, {width}, {height}, {real_width},
+        /// {real_height}, {scale}, {x}, {y}, {image} (path of the image being displayed, or
+        /// nothing), {color} (rrggbb color being displayed, or nothing), {gradient} (comma
+        /// separated colors of the gradient being displayed, or nothing), {animating} and
+        /// {transitioning} (true or false). Use {{ and }} for literal braces.
+        #[arg(long, conflicts_with = "json")]
+        format: Option<String>,
+    },
+
+    /// Waits until the transitions at the given outputs are done.
+    ///
+    /// Returns immediately if there are none. Animations are not waited for, since they never end.
+    Wait {
+        /// Comma separated list of outputs to wait for.
+        ///
+        /// If it isn't set, waits for all outputs.
+        #[arg(short, long, default_value = "")]
+        outputs: String,
+    },
+
+    /// Prints what happens in the daemon as it happens, as one line of JSON per event.
+    ///
+    /// Every event has an "event" and an "output" key. The events are "output-added",
+    /// "output-removed", "output-reconfigured" (along with the output's new dimensions and
+    /// position), "wallpaper-changed" (along with "image", "color" and "gradient", like `swww query
+    /// --json`), "transition-started", "transition-finished", "animation-started" and
+    /// "animation-stopped". Keeps running until the daemon exits.
+    Subscribe,
 }
 
 #[derive(Parser)]
 pub struct Clear {
     /// Color to fill the screen with.
     ///
-    /// Must be given in rrggbb format (note there is no prepended '#').
-    #[arg(value_parser = from_hex, default_value = "000000")]
-    pub color: [u8; 3],
+    /// Accepts '#rrggbb', '#rgb', 'rrggbbaa', 'rgb(r, g, b)', 'hsl(h, s%, l%)' and CSS color
+    /// names, like 'teal'. Alpha channels are ignored. Give a comma separated list of colors to
+    /// fill the screen with a gradient going through all of them, along with `--gradient` or
+    /// `--radial`.
+    #[arg(value_parser = parse_colors, default_value = "000000")]
+    pub color: Colors,
+
+    /// Fill the screen with a linear gradient at this angle, in degrees
+    ///
+    /// '0' goes from left to right, and '90' from bottom to top.
+    #[arg(long, allow_hyphen_values = true, conflicts_with = "radial")]
+    pub gradient: Option<f32>,
+
+    /// Fill the screen with a radial gradient centered at this position
+    ///
+    /// Accepts the same values as `swww img --transition-pos`, like '0.5,0.5' or 'top-left'. The
+    /// gradient reaches its last color at the farthest corner of the screen.
+    #[arg(long, value_parser = parse_coords)]
+    pub radial: Option<CliPosition>,
 
     /// Comma separated list of outputs to display the image at.
     ///
     /// If it isn't set, the image is displayed on all outputs.
     #[clap(short, long, default_value = "")]
     pub outputs: String,
+
+    #[command(flatten)]
+    pub transition_opts: TransitionOpts,
+}
+
+#[derive(Parser)]
+pub struct Restore {
+    /// Comma separated list of outputs to restore.
+    ///
+    /// If it isn't set, all outputs are restored.
+    #[arg(short, long, default_value = "")]
+    pub outputs: String,
+
+    #[command(flatten)]
+    pub transition_opts: TransitionOpts,
+}
+
+#[derive(Subcommand)]
+pub enum Cache {
+    /// Lists the processed images, from the most to the least recently used
+    List,
+
+    /// Deletes all processed images
+    Clear,
+
+    /// Deletes the least recently used processed images until the cache fits in its size limit
+    Prune(CachePrune),
+}
+
+#[derive(Parser)]
+pub struct CachePrune {
+    /// Maximum size of the cache, in bytes, optionally followed by K, M or G
+    ///
+    /// `swww img` also prunes the cache down to this size every time it runs, so set the
+    /// environment variable to change the limit for it too.
+    #[arg(
+        long,
+        env = "SWWW_CACHE_MAX_SIZE",
+        default_value = "512M",
+        value_parser = parse_size
+    )]
+    pub max_size: u64,
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
@@ -193,19 +428,92 @@ pub enum ResizeStrategy {
     Crop,
     /// Resize the image to fit inside the screen, preserving the original aspect ratio
     Fit,
+    /// Resize the image to exactly the screen's size, ignoring the original aspect ratio
+    Stretch,
+    /// Repeat the image across the whole screen, without resizing it
+    ///
+    /// Useful for small patterns. `--align` and `--offset` move the pattern around, and `--zoom`
+    /// scales every tile.
+    Tile,
+    /// Scale the image by the largest whole number that fits inside the screen, then pad it
+    ///
+    /// Combined with `--filter Nearest`, this keeps pixel art crisp. Images that are already
+    /// larger than the screen are not resized.
+    Integer,
+    /// Span the image across all selected outputs, according to their position
+    ///
+    /// The image is resized to fill the area covered by all outputs together (cropping out parts
+    /// that don't fit), and every output displays its part of it. If the outputs are moved or
+    /// reconfigured, the daemon spans the image again. See also `--bezel`.
+    Span,
 }
 
 #[derive(Parser)]
 pub struct Img {
-    /// Path to the image to display
-    pub path: PathBuf,
+    /// Path to the image to display, optionally prefixed by the outputs to display it at
+    ///
+    /// Use 'OUTPUT=PATH' (or 'OUTPUT1,OUTPUT2=PATH') to display different images at different
+    /// outputs with a single command, which also makes all their transitions start together. A
+    /// path without outputs is displayed at the outputs in `--outputs`, or, if that isn't set, at
+    /// every output that wasn't assigned an image. Use '-' to read the image from stdin.
+    #[arg(required = true, value_parser = parse_image)]
+    pub images: Vec<CliImage>,
 
     /// Comma separated list of outputs to display the image at.
     ///
-    /// If it isn't set, the image is displayed on all outputs.
+    /// This only applies to the image that wasn't assigned outputs with 'OUTPUT=PATH'. If it isn't
+    /// set, that image is displayed on all outputs that weren't assigned another image.
     #[arg(short, long, default_value = "")]
     pub outputs: String,
 
+    /// Use the options of this preset from the config file
+    ///
+    /// The preset overrides the config file's defaults and per-output options, but options given in
+    /// the command line or through environment variables still take priority over it.
+    #[arg(long)]
+    pub preset: Option<String>,
+
+    /// Read the images as raw, uncompressed pixels of the given size
+    ///
+    /// Given as WIDTHxHEIGHT, optionally followed by the pixels' format: ':rgba' (the default),
+    /// ':rgb' or ':bgra'. Pixels go row by row from the top-left corner, with one byte per channel.
+    /// This applies to every image given to the command, including the one read from stdin.
+    #[arg(long, value_parser = parse_raw)]
+    pub raw: Option<RawImage>,
+
+    #[command(flatten)]
+    pub resize_opts: ResizeOpts,
+
+    #[command(flatten)]
+    pub adjust_opts: AdjustOpts,
+
+    ///Sync the animations' frames between the monitors
+    ///
+    ///Activating this will cause a monitor's animation to wait until all other monitor's are
+    ///ready to update, and then update them all at once. Note if you want to send different `gif`s
+    ///to different monitors and have them synced, you will have to call `swww img --sync` **every
+    ///time**.
+    ///
+    ///Furthermore, if you only set `--sync` to one monitor and not the others, that monitor will
+    ///not animate anything, while the others will animate out-of-sync.
+    ///
+    ///Finally, note this only applies to animated images, not the transitions.
+    #[arg(long, default_value = "false")]
+    pub sync: bool,
+
+    /// Only exit once the transitions to the new images are done
+    ///
+    /// Otherwise, we exit as soon as the daemon has received the images. This can't be set in the
+    /// config file.
+    #[arg(long)]
+    pub wait: bool,
+
+    #[command(flatten)]
+    pub transition_opts: TransitionOpts,
+}
+
+#[derive(Args)]
+pub struct ResizeOpts {
     /// Do not resize the image. Equivalent to `--resize=no`
     ///
     /// If this is set, the image won't be resized, and will be centralized in the middle of the
@@ -224,9 +532,38 @@ pub struct Img {
     pub resize: ResizeStrategy,
 
     /// Which color to fill the padding with when output image does not fill screen
-    #[arg(value_parser = from_hex, long, default_value = "000000")]
+    ///
+    /// Accepts the same formats as `swww clear`, like '#282828' or 'rgb(40, 40, 40)'.
+    #[arg(value_parser = parse_color, long, default_value = "000000")]
     pub fill_color: [u8; 3],
 
+    /// How many logical pixels of the image to hide between two neighbouring outputs when using
+    /// `--resize span`, to compensate for the monitors' bezels
+    #[arg(long, default_value = "0")]
+    pub bezel: u32,
+
+    /// Which point of the image to align with the same point of the screen
+    ///
+    /// Takes the same values as `--transition-pos`, so both keywords like 'top-left' and x,y
+    /// coordinates from the bottom-left are accepted. With `--resize crop`, this decides which
+    /// parts of the image are cropped out; with `--resize fit` and `--resize no`, where the
+    /// padding goes.
+    #[arg(long, value_parser = parse_coords, default_value = "center")]
+    pub align: CliPosition,
+
+    /// How many pixels to move the image by after aligning it, as in x,y
+    ///
+    /// Positive values move the image to the right and up.
+    #[arg(long, value_parser = parse_offset, default_value = "0,0", allow_hyphen_values = true)]
+    pub offset: (i32, i32),
+
+    /// Scales the image by this much after resizing it
+    ///
+    /// Values above 1 zoom in, cropping out more of the image, while values below 1 zoom out,
+    /// padding it with the `--fill-color`.
+    #[arg(long, value_parser = parse_zoom, default_value = "1.0")]
+    pub zoom: f32,
+
     ///Filter to use when scaling images (run swww img --help to see options).
     ///
     ///Available options are:
@@ -243,26 +580,48 @@ pub struct Img {
     ///all slower than Nearest.
     #[arg(short, long, default_value = "Lanczos3")]
     pub filter: Filter,
+}
 
-    ///Sync the animations' frames between the monitors
-    ///
-    ///Activating this will cause a monitor's animation to wait until all other monitor's are
-    ///ready to update, and then update them all at once. Note if you want to send different `gif`s
-    ///to different monitors and have them synced, you will have to call `swww img --sync` **every
-    ///time**.
-    ///
-    ///Furthermore, if you only set `--sync` to one monitor and not the others, that monitor will
-    ///not animate anything, while the others will animate out-of-sync.
+/// Adjustments applied to the image after resizing it, in the order they are listed here
+#[derive(Args)]
+pub struct AdjustOpts {
+    /// Blurs the image. The value is the standard deviation of the gaussian blur, in pixels
+    #[arg(long, value_parser = parse_factor, default_value = "0")]
+    pub blur: f32,
+
+    /// Multiplies the image's brightness. Values below 1 darken it, and values above 1 brighten it
+    #[arg(long, value_parser = parse_factor, default_value = "1.0")]
+    pub brightness: f32,
+
+    /// Multiplies the image's contrast. Values below 1 reduce it, and values above 1 increase it
+    #[arg(long, value_parser = parse_factor, default_value = "1.0")]
+    pub contrast: f32,
+
+    /// Multiplies the image's saturation. 0 makes it grayscale
+    #[arg(long, value_parser = parse_factor, default_value = "1.0")]
+    pub saturation: f32,
+
+    /// Makes the image grayscale. Equivalent to `--saturation 0`
+    #[arg(long)]
+    pub grayscale: bool,
+
+    /// Tints the image by multiplying it with this color
     ///
-    ///Finally, note this only applies to `gif` animations, not the transitions.
-    #[arg(long, default_value = "false")]
-    pub sync: bool,
+    /// Accepts the same formats as `swww clear`, like 'ffd0a0' or 'hsl(30, 100%, 80%)'.
+    #[arg(long, value_parser = parse_color)]
+    pub tint: Option<[u8; 3]>,
+}
 
+#[derive(Args)]
+pub struct TransitionOpts {
     ///Sets the type of transition. Default is 'simple', that fades into the new image
     ///
     ///Possible transitions are:
     ///
-    ///simple | left | right | top | bottom | wipe | wave | grow | center | any | outer | random
+    ///simple | fade | left | right | top | bottom | wipe | wave | grow | center | any | outer | random
+    ///
+    ///'fade' crossfades into the new image, following `--transition-bezier` and taking exactly
+    ///`--transition-duration` seconds, no matter how different the images are.
     ///
     ///The 'left', 'right', 'top' and 'bottom' options make the transition happen from that
     ///position to its opposite in the screen.
@@ -303,7 +662,8 @@ pub struct Img {
 
     ///How long the transition takes to complete in seconds.
     ///
-    ///Note that this doesn't work with the 'simple' transition
+    ///Note that this doesn't work with the 'simple' transition. Use 'fade' for a fade that
+    ///takes exactly this long
     #[arg(long, env = "SWWW_TRANSITION_DURATION", default_value = "3")]
     pub transition_duration: f32,
 
@@ -346,6 +706,164 @@ pub struct Img {
     pub transition_wave: (f32, f32),
 }
 
+#[derive(Subcommand)]
+pub enum Playlist {
+    /// Starts a new playlist
+    ///
+    /// This replaces any playlist that was already running in the selected outputs.
+    Start(PlaylistStart),
+
+    /// Immediately switches to the next image in the playlist
+    Next(PlaylistTarget),
+
+    /// Immediately switches back to the previous image in the playlist
+    Prev(PlaylistTarget),
+
+    /// Pauses the playlist, keeping the current image on screen
+    Pause(PlaylistTarget),
+
+    /// Resumes a paused playlist
+    Resume(PlaylistTarget),
+
+    /// Stops the playlist, keeping the current image on screen
+    Stop(PlaylistTarget),
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum PlaylistOrder {
+    /// Go through the images in alphabetical order
+    Sequential,
+    /// Go through the images in a random order, without repeating any until all were shown
+    Shuffle,
+}
+
+#[derive(Parser)]
+pub struct PlaylistStart {
+    /// Images or directories of images to cycle through
+    ///
+    /// Directories are scanned again every time the playlist restarts, so images added to them
+    /// will be picked up. Note animated images will only display their first frame.
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Comma separated list of outputs to run the playlist at.
+    ///
+    /// If it isn't set, the playlist runs on all outputs.
+    #[arg(short, long, default_value = "")]
+    pub outputs: String,
+
+    /// How long each image stays on screen, in seconds. Must be at least 1
+    #[arg(short, long, default_value = "300", value_parser = clap::value_parser!(u32).range(1..))]
+    pub interval: u32,
+
+    /// The order in which to go through the images
+    #[arg(long, default_value = "sequential")]
+    pub order: PlaylistOrder,
+
+    #[command(flatten)]
+    pub resize_opts: ResizeOpts,
+
+    #[command(flatten)]
+    pub transition_opts: TransitionOpts,
+}
+
+#[derive(Parser)]
+pub struct PlaylistTarget {
+    /// Comma separated list of outputs whose playlists should be affected.
+    ///
+    /// If it isn't set, all playlists are affected.
+    #[arg(short, long, default_value = "")]
+    pub outputs: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CliImage {
+    /// Outputs this image was assigned to with the 'OUTPUT=PATH' syntax. Empty if there were none
+    pub outputs: Vec<String>,
+    pub path: PathBuf,
+}
+
+/// The layout of the pixels of a `--raw` image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RawFormat {
+    Rgba,
+    Rgb,
+    Bgra,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RawImage {
+    pub width: u32,
+    pub height: u32,
+    pub format: RawFormat,
+}
+
+fn parse_image(raw: &str) -> Result<CliImage, String> {
+    // file names may contain a '=' too, so we only split if the whole thing isn't a file
+    if raw == "-" || Path::new(raw).exists() {
+        return Ok(CliImage {
+            outputs: Vec::new(),
+            path: PathBuf::from(raw),
+        });
+    }
+
+    match raw.split_once('=') {
+        Some((outputs, path)) => {
+            let outputs: Vec<String> = outputs
+                .split(',')
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_owned())
+                .collect();
+            if outputs.is_empty() {
+                return Err(format!("no outputs before '=' in: {raw}"));
+            }
+            if path.is_empty() {
+                return Err(format!("no image path after '=' in: {raw}"));
+            }
+            Ok(CliImage {
+                outputs,
+                path: PathBuf::from(path),
+            })
+        }
+        None => Ok(CliImage {
+            outputs: Vec::new(),
+            path: PathBuf::from(raw),
+        }),
+    }
+}
+
+fn parse_raw(raw: &str) -> Result<RawImage, String> {
+    let (dim, format) = match raw.split_once(':') {
+        Some((dim, format)) => (dim, format),
+        None => (raw, "rgba"),
+    };
+    let format = match format.to_lowercase().as_str() {
+        "rgba" => RawFormat::Rgba,
+        "rgb" => RawFormat::Rgb,
+        "bgra" => RawFormat::Bgra,
+        _ => {
+            return Err(format!(
+                "unknown pixel format '{format}' (expected rgba, rgb or bgra)"
+            ))
+        }
+    };
+
+    let (width, height) = match dim.split_once(['x', 'X']) {
+        Some(dim) => dim,
+        None => return Err(format!("expected WIDTHxHEIGHT, got '{dim}'")),
+    };
+    let parse = |n: &str| match n.trim().parse::<u32>() {
+        Ok(n) if n > 0 => Ok(n),
+        Ok(_) => Err("the image's dimensions must be larger than 0".to_string()),
+        Err(e) => Err(format!("invalid dimension '{n}': {e}")),
+    };
+    Ok(RawImage {
+        width: parse(width)?,
+        height: parse(height)?,
+        format,
+    })
+}
+
 fn parse_wave(raw: &str) -> Result<(f32, f32), String> {
     let mut iter = raw.split(',');
     let mut parse = || {
@@ -358,6 +876,53 @@ fn parse_wave(raw: &str) -> Result<(f32, f32), String> {
     Ok(parsed)
 }
 
+fn parse_offset(raw: &str) -> Result<(i32, i32), String> {
+    let mut iter = raw.split(',');
+    let mut parse = || {
+        iter.next()
+            .ok_or_else(|| "Not enough values".to_string())
+            .and_then(|s| s.trim().parse::<i32>().map_err(|e| e.to_string()))
+    };
+
+    let parsed = (parse()?, parse()?);
+    if iter.next().is_some() {
+        return Err("Too many values (expected x,y)".to_string());
+    }
+    Ok(parsed)
+}
+
+fn parse_zoom(raw: &str) -> Result<f32, String> {
+    match raw.parse::<f32>() {
+        Ok(zoom) if zoom > 0.0 && zoom.is_finite() => Ok(zoom),
+        Ok(zoom) => Err(format!("zoom must be a positive number, got {zoom}")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_factor(raw: &str) -> Result<f32, String> {
+    match raw.parse::<f32>() {
+        Ok(factor) if factor >= 0.0 && factor.is_finite() => Ok(factor),
+        Ok(factor) => Err(format!("expected a non negative number, got {factor}")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_size(raw: &str) -> Result<u64, String> {
+    let raw = raw.trim();
+    let (number, multiplier) = match raw.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&raw[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&raw[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&raw[..i], 1 << 30),
+        _ => (raw, 1),
+    };
+    match number.trim().parse::<u64>() {
+        Ok(size) => size
+            .checked_mul(multiplier)
+            .ok_or_else(|| format!("size is too large: {raw}")),
+        Err(e) => Err(format!("invalid size '{raw}': {e}")),
+    }
+}
+
 fn parse_bezier(raw: &str) -> Result<(f32, f32, f32, f32), String> {
     let mut iter = raw.split(',');
     let mut parse = || {
@@ -458,35 +1023,313 @@ fn parse_coords(raw: &str) -> Result<CliPosition, String> {
     Ok(CliPosition::new(parsed_x, parsed_y))
 }
 
+/// The CSS named colors, sorted by name
+const NAMED_COLORS: &[(&str, [u8; 3])] = &[
+    ("aliceblue", [240, 248, 255]),
+    ("antiquewhite", [250, 235, 215]),
+    ("aqua", [0, 255, 255]),
+    ("aquamarine", [127, 255, 212]),
+    ("azure", [240, 255, 255]),
+    ("beige", [245, 245, 220]),
+    ("bisque", [255, 228, 196]),
+    ("black", [0, 0, 0]),
+    ("blanchedalmond", [255, 235, 205]),
+    ("blue", [0, 0, 255]),
+    ("blueviolet", [138, 43, 226]),
+    ("brown", [165, 42, 42]),
+    ("burlywood", [222, 184, 135]),
+    ("cadetblue", [95, 158, 160]),
+    ("chartreuse", [127, 255, 0]),
+    ("chocolate", [210, 105, 30]),
+    ("coral", [255, 127, 80]),
+    ("cornflowerblue", [100, 149, 237]),
+    ("cornsilk", [255, 248, 220]),
+    ("crimson", [220, 20, 60]),
+    ("cyan", [0, 255, 255]),
+    ("darkblue", [0, 0, 139]),
+    ("darkcyan", [0, 139, 139]),
+    ("darkgoldenrod", [184, 134, 11]),
+    ("darkgray", [169, 169, 169]),
+    ("darkgreen", [0, 100, 0]),
+    ("darkgrey", [169, 169, 169]),
+    ("darkkhaki", [189, 183, 107]),
+    ("darkmagenta", [139, 0, 139]),
+    ("darkolivegreen", [85, 107, 47]),
+    ("darkorange", [255, 140, 0]),
+    ("darkorchid", [153, 50, 204]),
+    ("darkred", [139, 0, 0]),
+    ("darksalmon", [233, 150, 122]),
+    ("darkseagreen", [143, 188, 143]),
+    ("darkslateblue", [72, 61, 139]),
+    ("darkslategray", [47, 79, 79]),
+    ("darkslategrey", [47, 79, 79]),
+    ("darkturquoise", [0, 206, 209]),
+    ("darkviolet", [148, 0, 211]),
+    ("deeppink", [255, 20, 147]),
+    ("deepskyblue", [0, 191, 255]),
+    ("dimgray", [105, 105, 105]),
+    ("dimgrey", [105, 105, 105]),
+    ("dodgerblue", [30, 144, 255]),
+    ("firebrick", [178, 34, 34]),
+    ("floralwhite", [255, 250, 240]),
+    ("forestgreen", [34, 139, 34]),
+    ("fuchsia", [255, 0, 255]),
+    ("gainsboro", [220, 220, 220]),
+    ("ghostwhite", [248, 248, 255]),
+    ("gold", [255, 215, 0]),
+    ("goldenrod", [218, 165, 32]),
+    ("gray", [128, 128, 128]),
+    ("green", [0, 128, 0]),
+    ("greenyellow", [173, 255, 47]),
+    ("grey", [128, 128, 128]),
+    ("honeydew", [240, 255, 240]),
+    ("hotpink", [255, 105, 180]),
+    ("indianred", [205, 92, 92]),
+    ("indigo", [75, 0, 130]),
+    ("ivory", [255, 255, 240]),
+    ("khaki", [240, 230, 140]),
+    ("lavender", [230, 230, 250]),
+    ("lavenderblush", [255, 240, 245]),
+    ("lawngreen", [124, 252, 0]),
+    ("lemonchiffon", [255, 250, 205]),
+    ("lightblue", [173, 216, 230]),
+    ("lightcoral", [240, 128, 128]),
+    ("lightcyan", [224, 255, 255]),
+    ("lightgoldenrodyellow", [250, 250, 210]),
+    ("lightgray", [211, 211, 211]),
+    ("lightgreen", [144, 238, 144]),
+    ("lightgrey", [211, 211, 211]),
+    ("lightpink", [255, 182, 193]),
+    ("lightsalmon", [255, 160, 122]),
+    ("lightseagreen", [32, 178, 170]),
+    ("lightskyblue", [135, 206, 250]),
+    ("lightslategray", [119, 136, 153]),
+    ("lightslategrey", [119, 136, 153]),
+    ("lightsteelblue", [176, 196, 222]),
+    ("lightyellow", [255, 255, 224]),
+    ("lime", [0, 255, 0]),
+    ("limegreen", [50, 205, 50]),
+    ("linen", [250, 240, 230]),
+    ("magenta", [255, 0, 255]),
+    ("maroon", [128, 0, 0]),
+    ("mediumaquamarine", [102, 205, 170]),
+    ("mediumblue", [0, 0, 205]),
+    ("mediumorchid", [186, 85, 211]),
+    ("mediumpurple", [147, 112, 219]),
+    ("mediumseagreen", [60, 179, 113]),
+    ("mediumslateblue", [123, 104, 238]),
+    ("mediumspringgreen", [0, 250, 154]),
+    ("mediumturquoise", [72, 209, 204]),
+    ("mediumvioletred", [199, 21, 133]),
+    ("midnightblue", [25, 25, 112]),
+    ("mintcream", [245, 255, 250]),
+    ("mistyrose", [255, 228, 225]),
+    ("moccasin", [255, 228, 181]),
+    ("navajowhite", [255, 222, 173]),
+    ("navy", [0, 0, 128]),
+    ("oldlace", [253, 245, 230]),
+    ("olive", [128, 128, 0]),
+    ("olivedrab", [107, 142, 35]),
+    ("orange", [255, 165, 0]),
+    ("orangered", [255, 69, 0]),
+    ("orchid", [218, 112, 214]),
+    ("palegoldenrod", [238, 232, 170]),
+    ("palegreen", [152, 251, 152]),
+    ("paleturquoise", [175, 238, 238]),
+    ("palevioletred", [219, 112, 147]),
+    ("papayawhip", [255, 239, 213]),
+    ("peachpuff", [255, 218, 185]),
+    ("peru", [205, 133, 63]),
+    ("pink", [255, 192, 203]),
+    ("plum", [221, 160, 221]),
+    ("powderblue", [176, 224, 230]),
+    ("purple", [128, 0, 128]),
+    ("rebeccapurple", [102, 51, 153]),
+    ("red", [255, 0, 0]),
+    ("rosybrown", [188, 143, 143]),
+    ("royalblue", [65, 105, 225]),
+    ("saddlebrown", [139, 69, 19]),
+    ("salmon", [250, 128, 114]),
+    ("sandybrown", [244, 164, 96]),
+    ("seagreen", [46, 139, 87]),
+    ("seashell", [255, 245, 238]),
+    ("sienna", [160, 82, 45]),
+    ("silver", [192, 192, 192]),
+    ("skyblue", [135, 206, 235]),
+    ("slateblue", [106, 90, 205]),
+    ("slategray", [112, 128, 144]),
+    ("slategrey", [112, 128, 144]),
+    ("snow", [255, 250, 250]),
+    ("springgreen", [0, 255, 127]),
+    ("steelblue", [70, 130, 180]),
+    ("tan", [210, 180, 140]),
+    ("teal", [0, 128, 128]),
+    ("thistle", [216, 191, 216]),
+    ("tomato", [255, 99, 71]),
+    ("turquoise", [64, 224, 208]),
+    ("violet", [238, 130, 238]),
+    ("wheat", [245, 222, 179]),
+    ("white", [255, 255, 255]),
+    ("whitesmoke", [245, 245, 245]),
+    ("yellow", [255, 255, 0]),
+    ("yellowgreen", [154, 205, 50]),
+];
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn should_parse_placement() {
+        assert_eq!(parse_offset("-10, 20"), Ok((-10, 20)));
+        assert!(parse_offset("10").is_err(), "accepting a single value");
+        assert!(parse_offset("1,2,3").is_err(), "accepting three values");
+        assert_eq!(parse_zoom("1.5"), Ok(1.5));
+        assert!(parse_zoom("0").is_err(), "accepting a zoom of 0");
+        assert!(parse_zoom("-2").is_err(), "accepting a negative zoom");
+    }
+
+    #[test]
+    fn should_parse_raw_images() {
+        assert_eq!(
+            parse_raw("1920x1080"),
+            Ok(RawImage {
+                width: 1920,
+                height: 1080,
+                format: RawFormat::Rgba,
+            })
+        );
+        assert_eq!(
+            parse_raw("2x3:bgra").map(|raw| raw.format),
+            Ok(RawFormat::Bgra)
+        );
+        assert_eq!(
+            parse_raw("2X3:RGB").map(|raw| raw.format),
+            Ok(RawFormat::Rgb)
+        );
+        assert!(parse_raw("1920").is_err(), "accepting a single dimension");
+        assert!(parse_raw("0x10").is_err(), "accepting an empty image");
+        assert!(
+            parse_raw("2x3:argb").is_err(),
+            "accepting an unknown format"
+        );
+    }
+
+    #[test]
+    fn should_parse_sizes() {
+        assert_eq!(parse_size("1000"), Ok(1000));
+        assert_eq!(parse_size("2K"), Ok(2048));
+        assert_eq!(parse_size("512M"), Ok(512 << 20));
+        assert_eq!(parse_size("1g"), Ok(1 << 30));
+        assert!(
+            parse_size("M").is_err(),
+            "accepting a size without a number"
+        );
+        assert!(parse_size("1.5G").is_err(), "accepting a fractional size");
+        assert!(parse_size("100T").is_err(), "accepting an unknown suffix");
+    }
+
     #[test]
     fn should_reject_wrong_colors() {
         assert!(
-            from_hex("0012231").is_err(),
+            parse_color("0012231").is_err(),
             "function is accepting strings with more than 6 chars"
         );
         assert!(
-            from_hex("00122").is_err(),
+            parse_color("00122").is_err(),
             "function is accepting strings with less than 6 chars"
         );
         assert!(
-            from_hex("00r223").is_err(),
+            parse_color("00r223").is_err(),
             "function is accepting strings with chars that aren't hex"
         );
+        assert!(
+            parse_color("#10-10-10").is_err(),
+            "function is accepting separators between hex digits"
+        );
+        assert!(
+            parse_color("rgb(1, 2)").is_err(),
+            "function is accepting rgb with 2 values"
+        );
+        assert!(
+            parse_color("rgb(256, 0, 0)").is_err(),
+            "function is accepting rgb values over 255"
+        );
+        assert!(
+            parse_color("hsl(0, 120%, 50%)").is_err(),
+            "function is accepting a saturation over 100%"
+        );
+        assert!(
+            parse_color("notacolor").is_err(),
+            "function is accepting unknown names"
+        );
     }
 
     #[test]
     fn should_convert_colors_from_hex() {
-        let color = from_hex("101010").unwrap();
+        let color = parse_color("101010").unwrap();
         assert_eq!(color, [16, 16, 16]);
 
-        let color = from_hex("ffffff").unwrap();
+        let color = parse_color("ffffff").unwrap();
         assert_eq!(color, [255, 255, 255]);
 
-        let color = from_hex("000000").unwrap();
+        let color = parse_color("000000").unwrap();
         assert_eq!(color, [0, 0, 0]);
+
+        assert_eq!(parse_color("#1E1E2E"), Ok([30, 30, 46]));
+        assert_eq!(parse_color("#f80"), Ok([255, 136, 0]));
+        assert_eq!(parse_color("1e1e2e80"), Ok([30, 30, 46]));
+    }
+
+    #[test]
+    fn should_convert_colors_from_functions_and_names() {
+        assert_eq!(parse_color("rgb(30, 30, 46)"), Ok([30, 30, 46]));
+        assert_eq!(parse_color("rgba(100%,0%,50%,0.5)"), Ok([255, 0, 128]));
+        assert_eq!(parse_color("rgb(30 30 46 / 50%)"), Ok([30, 30, 46]));
+        assert_eq!(parse_color("hsl(0, 100%, 50%)"), Ok([255, 0, 0]));
+        assert_eq!(parse_color("hsla(120deg 100% 25% / 1)"), Ok([0, 128, 0]));
+        assert_eq!(parse_color("hsl(240, 50%, 50%)"), Ok([64, 64, 191]));
+        assert_eq!(parse_color("RebeccaPurple"), Ok([102, 51, 153]));
+        assert!(
+            NAMED_COLORS.windows(2).all(|w| w[0].0 < w[1].0),
+            "named colors aren't sorted"
+        );
+    }
+
+    #[test]
+    fn should_split_color_lists() {
+        assert_eq!(
+            parse_colors("1e1e2e,rgb(49, 50, 68),teal").map(|colors| colors.0),
+            Ok(vec![[30, 30, 46], [49, 50, 68], [0, 128, 128]])
+        );
+        assert!(parse_colors("1e1e2e,").is_err(), "accepting an empty color");
+    }
+
+    #[test]
+    fn should_parse_images_assigned_to_outputs() {
+        let img = parse_image("DP-1=a.png").unwrap();
+        assert_eq!(img.outputs, vec!["DP-1".to_string()]);
+        assert_eq!(img.path, PathBuf::from("a.png"));
+
+        let img = parse_image("DP-1,HDMI-A-1=dir/b=c.jpg").unwrap();
+        assert_eq!(
+            img.outputs,
+            vec!["DP-1".to_string(), "HDMI-A-1".to_string()]
+        );
+        assert_eq!(img.path, PathBuf::from("dir/b=c.jpg"));
+
+        let img = parse_image("a.png").unwrap();
+        assert!(img.outputs.is_empty());
+        assert_eq!(img.path, PathBuf::from("a.png"));
+
+        let img = parse_image("-").unwrap();
+        assert!(img.outputs.is_empty());
+    }
+
+    #[test]
+    fn should_reject_incomplete_image_assignments() {
+        assert!(parse_image("=a.png").is_err(), "accepting missing outputs");
+        assert!(parse_image(",=a.png").is_err(), "accepting empty outputs");
+        assert!(parse_image("DP-1=").is_err(), "accepting missing path");
     }
 }
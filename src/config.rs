@@ -0,0 +1,205 @@
+//! Applies the config file to `swww img`'s options. See `utils::config` for the file's format.
+//!
+//! The config's options are turned into command line arguments, and parsed along with the ones the
+//! user gave, so that they go through the exact same validation as the real thing.
+
+use clap::{parser::ValueSource, ArgMatches, CommandFactory, Parser};
+use std::ffi::{OsStr, OsString};
+
+use utils::config::{Config, Section};
+
+use crate::cli::Img;
+
+/// These would change which outputs the options apply to, how the image itself is read, or how the
+/// command behaves, so they can not be in the config file
+const FORBIDDEN: &[&str] = &["outputs", "preset", "raw", "wait", "help"];
+
+/// Splits `outputs` into groups that end up with the same options once the config file is applied.
+///
+/// Options given in the command line or through environment variables always take priority over
+/// the config file. `matches` are what `img` was parsed into.
+pub fn img_options(
+    config: &Config,
+    preset: Option<&str>,
+    outputs: &[String],
+    matches: &ArgMatches,
+) -> Result<Vec<(Img, Vec<String>)>, String> {
+    let cmdline = cmdline_args(matches);
+    let mut groups: Vec<(Vec<String>, Vec<String>)> = Vec::new();
+    for output in outputs {
+        let args = config_args(&config.options_for(output, preset)?, matches)?;
+        match groups
+            .iter_mut()
+            .find(|(group_args, _)| *group_args == args)
+        {
+            Some((_, group)) => group.push(output.clone()),
+            None => groups.push((args, vec![output.clone()])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(
+            |(args, outputs)| match Img::try_parse_from(with_name(&args, &cmdline)) {
+                Ok(img) => Ok((img, outputs)),
+                Err(e) => Err(format!("invalid option in config file: {e}")),
+            },
+        )
+        .collect()
+}
+
+/// Turns the options the user gave in the command line back into arguments. Those set through
+/// environment variables are left out, since parsing reads them again anyway
+fn cmdline_args(matches: &ArgMatches) -> Vec<OsString> {
+    let mut args = Vec::new();
+    let mut images = Vec::new();
+    for arg in Img::command().get_arguments() {
+        let id = arg.get_id().as_str();
+        if !matches!(matches.value_source(id), Some(ValueSource::CommandLine)) {
+            continue;
+        }
+        let values = matches.get_raw(id).into_iter().flatten();
+        match arg.get_long() {
+            None => images.extend(values.map(OsStr::to_os_string)),
+            Some(long) if arg.get_action().takes_values() => {
+                for value in values {
+                    let mut arg = OsString::from(format!("--{long}="));
+                    arg.push(value);
+                    args.push(arg);
+                }
+            }
+            Some(long) => args.push(OsString::from(format!("--{long}"))),
+        }
+    }
+    // images may start with a hyphen
+    args.push(OsString::from("--"));
+    args.extend(images);
+    args
+}
+
+/// Whether the user set `id`, rather than it having its default value
+fn set_by_user(matches: &ArgMatches, id: &str) -> bool {
+    matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    )
+}
+
+fn with_name(config_args: &[String], cmdline: &[OsString]) -> Vec<OsString> {
+    std::iter::once(OsString::from("img"))
+        .chain(config_args.iter().map(OsString::from))
+        .chain(cmdline.iter().cloned())
+        .collect()
+}
+
+/// Turns `options` into command line arguments, skipping the ones the user already set
+fn config_args(options: &Section, matches: &ArgMatches) -> Result<Vec<String>, String> {
+    let command = Img::command();
+    let mut args = Vec::new();
+    for (name, value) in options {
+        let arg = match command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(name.as_str()))
+        {
+            Some(arg) if !FORBIDDEN.contains(&name.as_str()) => arg,
+            _ => return Err(format!("unknown option in config file: {name}")),
+        };
+
+        // `--no-resize` only changes the default of `--resize`
+        let id = arg.get_id().as_str();
+        if set_by_user(matches, id) || (id == "resize" && set_by_user(matches, "no_resize")) {
+            continue;
+        }
+
+        if arg.get_action().takes_values() {
+            args.push(format!("--{name}={value}"));
+        } else {
+            match value.as_str() {
+                "true" => args.push(format!("--{name}")),
+                "false" => (),
+                _ => return Err(format!("{name} must be true or false in the config file")),
+            }
+        }
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(options: &[(&str, &str)]) -> Section {
+        options
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn command_line_should_override_config() {
+        let matches = Img::command()
+            .try_get_matches_from(["img", "--resize", "fit", "a.png"])
+            .unwrap();
+        let args = config_args(
+            &options(&[
+                ("resize", "crop"),
+                ("sync", "true"),
+                ("transition-fps", "60"),
+            ]),
+            &matches,
+        )
+        .unwrap();
+        assert_eq!(args, vec!["--sync", "--transition-fps=60"]);
+    }
+
+    #[test]
+    fn no_resize_should_override_config() {
+        let matches = Img::command()
+            .try_get_matches_from(["img", "--no-resize", "a.png"])
+            .unwrap();
+        let args = config_args(&options(&[("resize", "crop")]), &matches).unwrap();
+        assert!(args.is_empty());
+    }
+
+    #[test]
+    fn command_line_should_be_given_back() {
+        let matches = Img::command()
+            .try_get_matches_from([
+                "img",
+                "-o",
+                "DP-1",
+                "--grayscale",
+                "--offset",
+                "-5,0",
+                "--",
+                "-a",
+            ])
+            .unwrap();
+        let cmdline = cmdline_args(&matches);
+        let args = ["--outputs=DP-1", "--offset=-5,0", "--grayscale", "--", "-a"];
+        assert_eq!(cmdline, args.map(OsString::from));
+
+        let img = Img::try_parse_from(with_name(&["--sync".to_string()], &cmdline)).unwrap();
+        assert_eq!(img.outputs, "DP-1");
+        assert!(img.sync);
+    }
+
+    #[test]
+    fn should_reject_invalid_options() {
+        let matches = Img::command()
+            .try_get_matches_from(["img", "a.png"])
+            .unwrap();
+        assert!(
+            config_args(&options(&[("resise", "crop")]), &matches).is_err(),
+            "accepting unknown option"
+        );
+        assert!(
+            config_args(&options(&[("outputs", "DP-1")]), &matches).is_err(),
+            "accepting outputs"
+        );
+        assert!(
+            config_args(&options(&[("sync", "yes")]), &matches).is_err(),
+            "accepting non boolean flag"
+        );
+    }
+}
@@ -1,30 +1,58 @@
-use clap::Parser;
-use fast_image_resize::{FilterType, PixelType, Resizer};
-use image::{codecs::gif::GifDecoder, AnimationDecoder, RgbaImage};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+    AnimationDecoder, ImageFormat, RgbaImage,
+};
 use std::{
+    collections::BTreeMap,
     fs::File,
-    io::{stdin, BufReader, Read},
-    num::NonZeroU32,
-    os::unix::net::UnixStream,
-    path::{Path, PathBuf},
+    io::{stdin, BufRead, BufReader, BufWriter, Cursor, Read, Seek, Write},
+    path::Path,
     process::Stdio,
+    sync::{mpsc, Arc, Mutex},
     time::Duration,
 };
 
+use swww_client::{img, Client};
 use utils::{
-    communication::{self, get_socket_path, AnimationRequest, Answer, Coord, Position, Request},
+    communication::{
+        self, get_cache_path, set_namespace, AnimationFrame, Answer, BgInfo, Coord, Event,
+        Gradient, GradientShape, Position, Request, StreamedAnimation,
+    },
     comp_decomp::BitPack,
+    config::Config,
+    history::{self, Direction},
+    resize::resize,
 };
 
+mod adjust;
+mod cache;
 mod cli;
-use cli::{ResizeStrategy, Swww};
+mod config;
+mod query;
+use adjust::{adjust, adjust_rgba, has_adjustments};
+use cli::{Cli, ResizeStrategy, Swww};
 
 fn main() -> Result<(), String> {
-    let swww = Swww::parse();
+    // the matches are kept around, since applying the config file needs to know which options the
+    // user set
+    let matches = Cli::command().get_matches();
+    let cli = match Cli::from_arg_matches(&matches) {
+        Ok(cli) => cli,
+        Err(e) => e.exit(),
+    };
+    if let Some(namespace) = &cli.namespace {
+        set_namespace(namespace)?;
+    }
+    let swww = cli.command;
+    if let Swww::Cache(command) = &swww {
+        return manage_cache(command);
+    }
+    let mut client = Client::new();
     if let Swww::Init { no_daemon } = &swww {
-        match is_daemon_running() {
+        match client.is_running() {
             Ok(false) => {
-                let socket_path = get_socket_path();
+                let socket_path = client.socket_path();
                 if socket_path.exists() {
                     eprintln!(
                         "WARNING: socket file {} was not deleted when the previous daemon exited",
@@ -39,9 +67,9 @@ fn main() -> Result<(), String> {
                 return Err("There seems to already be another instance running...".to_string())
             }
             Err(e) => {
-                eprintln!("WARNING: failed to read '/proc' directory to determine whether the daemon is running: {e}
-                          Falling back to trying to checking if the socket file exists...");
-                let socket_path = get_socket_path();
+                eprintln!("WARNING: failed to connect to the socket to determine whether the daemon is running: {e}
+                          Falling back to checking if the socket file exists...");
+                let socket_path = client.socket_path();
                 if socket_path.exists() {
                     return Err(format!(
                         "Found socket at {}. There seems to be an instance already running...",
@@ -54,471 +82,1060 @@ fn main() -> Result<(), String> {
         if *no_daemon {
             return Ok(());
         }
+        // we may have just deleted the socket of an old daemon that crashed, and the new daemon
+        // listens somewhere else
+        client = Client::new();
     }
 
-    let request = make_request(&swww)?;
-    let socket = connect_to_socket(5, 100)?;
-    request.send(&socket)?;
-    match Answer::receive(socket)? {
-        Answer::Err(msg) => return Err(msg),
-        Answer::Info(info) => info.into_iter().for_each(|i| println!("{i}")),
-        Answer::Ok => {
-            if let Swww::Kill = swww {
-                #[cfg(debug_assertions)]
-                let tries = 20;
-                #[cfg(not(debug_assertions))]
-                let tries = 10;
-                let socket_path = get_socket_path();
-                for _ in 0..tries {
-                    if !socket_path.exists() {
-                        return Ok(());
-                    }
-                    std::thread::sleep(Duration::from_millis(100));
+    // a daemon speaking another protocol would misread our requests, but it can still be killed
+    if let Swww::Kill = swww {
+        return Ok(client.kill()?);
+    }
+    // this also waits for the daemon we might have just spawned to be ready
+    client.check_version()?;
+    if let Swww::Subscribe = swww {
+        return subscribe(&client);
+    }
+
+    let request = make_request(&swww, &client, &matches)?;
+    if let Swww::Img(_) = &swww {
+        prune_cache();
+    }
+    let request = match request {
+        Some(request) => request,
+        None => return Ok(()),
+    };
+    match client.request(&request)? {
+        Answer::Info(infos) => match &swww {
+            Swww::Query { json: true, .. } => println!("{}", query::to_json(&infos)),
+            Swww::Query {
+                format: Some(format),
+                ..
+            } => {
+                for info in &infos {
+                    println!("{}", query::format(format, info)?);
                 }
-                return Err(format!(
-                    "Could not confirm socket deletion at: {socket_path:?}"
-                ));
             }
+            _ => infos.iter().for_each(|i| println!("{i}")),
+        },
+        Answer::Ok => (),
+        // errors were already turned into `swww_client::Error::Daemon`, and none of our requests
+        // are answered with pings or configs, unless the daemon is misbehaving
+        Answer::Ping(_) | Answer::Config(_) | Answer::Err(_) => {
+            return Err(swww_client::Error::UnexpectedAnswer.into())
         }
     }
     Ok(())
 }
 
-fn make_request(args: &Swww) -> Result<Request, String> {
-    match args {
-        Swww::Clear(c) => Ok(Request::Clear(communication::Clear {
-            color: c.color,
-            outputs: split_cmdline_outputs(&c.outputs),
-        })),
+/// Returns `None` if there is nothing left to send. That happens when animations are streamed or
+/// when waiting for the transitions, and when stepping through the history, since those requests
+/// were already sent, and with `swww init`, which only had to wait for the daemon to answer its
+/// ping
+fn make_request(
+    args: &Swww,
+    client: &Client,
+    matches: &ArgMatches,
+) -> Result<Option<Request>, String> {
+    let request = match args {
+        Swww::Clear(c) => Request::Clear(make_clear(c)?),
         Swww::Img(img) => {
-            let requested_outputs = split_cmdline_outputs(&img.outputs);
-            let (dims, outputs) = get_dimensions_and_outputs(requested_outputs)?;
-            let (img_raw, is_gif) = read_img(&img.path)?;
-            if is_gif {
-                match std::thread::scope(|s| {
-                    let animations = s.spawn(|| make_animation_request(img, &dims, &outputs));
-                    let img_request = make_img_request(img, img_raw, &dims, &outputs)?;
-                    let animations = match animations.join() {
-                        Ok(a) => a,
-                        Err(e) => Err(format!("{e:?}")),
-                    };
-                    let socket = connect_to_socket(5, 100)?;
-                    Request::Img(img_request).send(&socket)?;
-                    Answer::receive(socket)?;
-                    animations
-                }) {
-                    Ok(animations) => Ok(Request::Animation(animations)),
-                    Err(e) => Err(format!("failed to create animated request: {e}")),
-                }
-            } else {
-                Ok(Request::Img(make_img_request(
-                    img, img_raw, &dims, &outputs,
-                )?))
+            let img_matches = match matches.subcommand_matches("img") {
+                Some(img_matches) => img_matches,
+                None => unreachable!("swww img was parsed from these matches"),
+            };
+            let infos = client.query()?;
+            let config = query_config(client, img.preset.is_some())?;
+            let mut assignments = Vec::new();
+            for (path, outputs) in assign_outputs(img, &infos)? {
+                let groups =
+                    config::img_options(&config, img.preset.as_deref(), &outputs, img_matches)?;
+                assignments.push((path, groups));
             }
+
+            return std::thread::scope(|s| {
+                let (frame_sender, frame_receiver) = mpsc::channel();
+                let mut stream = AnimationStream {
+                    animations: Vec::new(),
+                    frame_sender,
+                };
+                let mut animations = Vec::new();
+                let mut requests = Vec::new();
+                for (path, groups) in &assignments {
+                    let mut source = Source::open(path, img.raw.as_ref())?;
+                    for (img, requested_outputs) in groups {
+                        let mut imgs = Vec::new();
+                        animations.extend(make_img_requests(
+                            s,
+                            &mut imgs,
+                            &mut source,
+                            img,
+                            &infos,
+                            requested_outputs,
+                            &mut stream,
+                        )?);
+                        requests.push((make_transition(&img.transition_opts), imgs));
+                    }
+                }
+
+                // every image goes in the same request, so that all transitions start together
+                let img_request = Request::Img(requests);
+                if animations.is_empty() && !img.wait {
+                    return Ok(Some(img_request));
+                }
+
+                client.request(&img_request)?;
+                if !animations.is_empty() {
+                    // the stream ends once every thread making an animation is done with the
+                    // channel
+                    drop(stream.frame_sender);
+                    send_animation_stream(client, stream.animations, frame_receiver)?;
+                }
+                for animation in animations {
+                    match animation.join() {
+                        Ok(Ok(())) => (),
+                        Ok(Err(e)) => {
+                            return Err(format!("failed to create animated request: {e}"))
+                        }
+                        Err(e) => return Err(format!("failed to create animated request: {e:?}")),
+                    }
+                }
+                if img.wait {
+                    let outputs: Vec<String> = assignments
+                        .iter()
+                        .flat_map(|(_, groups)| groups.iter().flat_map(|(_, o)| o.clone()))
+                        .collect();
+                    client.wait(&outputs)?;
+                }
+                Ok(None)
+            });
         }
-        Swww::Init { .. } => Ok(Request::Init),
-        Swww::Kill => Ok(Request::Kill),
-        Swww::Query => Ok(Request::Query),
-    }
+        Swww::Playlist(playlist) => make_playlist_request(playlist)?,
+        Swww::Restore(restore) => Request::Restore(communication::Restore {
+            outputs: split_cmdline_outputs(&restore.outputs),
+            files: Vec::new(),
+            transition: make_transition(&restore.transition_opts),
+        }),
+        Swww::Back(restore) => return step_history(client, restore, Direction::Back).map(|_| None),
+        Swww::Forward(restore) => {
+            return step_history(client, restore, Direction::Forward).map(|_| None)
+        }
+        Swww::Cache(_) => unreachable!("the cache is managed without the daemon"),
+        Swww::Subscribe => unreachable!("subscriptions are handled by `subscribe`"),
+        Swww::Init { .. } => return Ok(None),
+        Swww::Kill => unreachable!("the daemon is killed by `Client::kill`"),
+        Swww::Query { .. } => Request::Query,
+        Swww::Wait { outputs } => Request::Wait(split_cmdline_outputs(outputs)),
+    };
+    Ok(Some(request))
 }
 
-fn split_cmdline_outputs(outputs: &str) -> Vec<String> {
-    outputs
-        .split(',')
-        .map(|s| s.to_owned())
-        .filter(|s| !s.is_empty())
-        .collect()
-}
+/// Prints every event the daemon sends as a line of JSON, until it exits
+fn subscribe(client: &Client) -> Result<(), String> {
+    let socket = client.connect()?;
+    Request::Subscribe.send(&socket)?;
+    let answer_socket = match socket.try_clone() {
+        Ok(socket) => socket,
+        Err(e) => return Err(format!("failed to clone socket: {e}")),
+    };
+    if let Answer::Err(e) = Answer::receive(answer_socket)? {
+        return Err(e);
+    }
+    // the clone shares the timeout `Answer::receive` set, but events may take forever to come
+    if let Err(e) = socket.set_read_timeout(None) {
+        return Err(format!("failed to unset read timeout: {e}"));
+    }
 
-fn read_img(path: &Path) -> Result<(RgbaImage, bool), String> {
-    if let Some("-") = path.to_str() {
-        let mut reader = BufReader::new(stdin());
-        let mut buffer = Vec::new();
-        if let Err(e) = reader.read_to_end(&mut buffer) {
-            return Err(format!("failed to read stdin: {e}"));
+    let mut reader = BufReader::new(socket);
+    let mut stdout = std::io::stdout().lock();
+    loop {
+        match reader.fill_buf() {
+            // the daemon exited
+            Ok([]) => return Ok(()),
+            Ok(_) => (),
+            Err(e) => return Err(format!("failed to receive event: {e}")),
+        }
+        let event = Event::receive(&mut reader)?;
+        if writeln!(stdout, "{}", query::event_to_json(&event)).is_err() {
+            // whoever was reading our output is gone
+            return Ok(());
         }
-
-        return match image::load_from_memory(&buffer) {
-            Ok(img) => Ok((img.into_rgba8(), false)),
-            Err(e) => return Err(format!("failed load image from memory: {e}")),
-        };
     }
+}
 
-    let imgbuf = match image::io::Reader::open(path) {
-        Ok(img) => img,
-        Err(e) => return Err(format!("failed to open image: {e}")),
+fn make_clear(c: &cli::Clear) -> Result<communication::Clear, String> {
+    let shape = match (c.gradient, &c.radial) {
+        (Some(angle), _) => Some(GradientShape::Linear { angle }),
+        (None, Some(center)) => Some(GradientShape::Radial {
+            center: make_position(center),
+        }),
+        (None, None) if c.color.0.len() > 1 => {
+            return Err("several colors can only be given with --gradient or --radial".to_string())
+        }
+        (None, None) => None,
     };
+    Ok(communication::Clear {
+        color: c.color.0[0],
+        gradient: shape.map(|shape| Gradient {
+            shape,
+            colors: c.color.0.clone(),
+        }),
+        outputs: split_cmdline_outputs(&c.outputs),
+        transition: make_transition(&c.transition_opts),
+    })
+}
+
+/// Animations whose frames are sent to the daemon as soon as they are ready. See
+/// `Request::AnimationStream`
+struct AnimationStream {
+    animations: Vec<StreamedAnimation>,
+    frame_sender: mpsc::Sender<AnimationFrame>,
+}
 
-    let imgbuf = match imgbuf.with_guessed_format() {
-        Ok(img) => img,
-        Err(e) => return Err(format!("failed to detect the image's format: {e}")),
+/// Sends the frames coming through `frames` to the daemon until every thread making them is done,
+/// then caches the animations that were completed
+fn send_animation_stream(
+    client: &Client,
+    animations: Vec<StreamedAnimation>,
+    frames: mpsc::Receiver<AnimationFrame>,
+) -> Result<(), String> {
+    let socket = client.connect()?;
+    Request::AnimationStream(animations.clone()).send(&socket)?;
+    let answer_socket = match socket.try_clone() {
+        Ok(socket) => socket,
+        Err(e) => return Err(format!("failed to clone socket: {e}")),
     };
+    if let Answer::Err(e) = Answer::receive(answer_socket)? {
+        return Err(e);
+    }
 
-    let is_gif = imgbuf.format() == Some(image::ImageFormat::Gif);
-    match imgbuf.decode() {
-        Ok(img) => Ok((img.into_rgba8(), is_gif)),
-        Err(e) => Err(format!("failed to decode image: {e}")),
+    let mut writer = BufWriter::new(&socket);
+    let mut made: Vec<Vec<(BitPack, Duration)>> = animations.iter().map(|_| Vec::new()).collect();
+    let mut complete = vec![false; animations.len()];
+    for frame in frames {
+        frame.send(&mut writer)?;
+        match frame {
+            AnimationFrame::Frame {
+                animation,
+                frame,
+                duration,
+            } => made[animation].push((frame, duration)),
+            AnimationFrame::End(animation) => complete[animation] = true,
+        }
     }
-}
 
-fn make_img_request(
-    img: &cli::Img,
-    img_raw: image::RgbaImage,
-    dims: &[(u32, u32)],
-    outputs: &[Vec<String>],
-) -> Result<communication::ImageRequest, String> {
-    let transition = make_transition(img);
-    let mut unique_requests = Vec::with_capacity(dims.len());
-    for (dim, outputs) in dims.iter().zip(outputs) {
-        unique_requests.push((
-            communication::Img {
-                img: match img.resize {
-                    ResizeStrategy::No => img_pad(img_raw.clone(), *dim, &img.fill_color)?,
-                    ResizeStrategy::Crop => {
-                        img_resize_crop(img_raw.clone(), *dim, make_filter(&img.filter))?
-                    }
-                    ResizeStrategy::Fit => img_resize_fit(
-                        img_raw.clone(),
-                        *dim,
-                        make_filter(&img.filter),
-                        &img.fill_color,
-                    )?,
+    let finished: Vec<(communication::Animation, Vec<String>)> = animations
+        .into_iter()
+        .zip(made)
+        .zip(complete)
+        .filter(|(_, complete)| *complete)
+        .map(|((animation, frames), _)| {
+            (
+                communication::Animation {
+                    animation: frames.into_boxed_slice(),
+                    sync: animation.sync,
                 },
-                path: match img.path.canonicalize() {
-                    Ok(p) => p,
-                    Err(e) => {
-                        if let Some("-") = img.path.to_str() {
-                            PathBuf::from("STDIN")
-                        } else {
-                            return Err(format!("failed no canonicalize image path: {e}"));
-                        }
-                    }
+                animation.outputs,
+            )
+        })
+        .collect();
+    match get_cache_path() {
+        Ok(cache_path) => Request::cache_animations(&finished, cache_path),
+        Err(e) => eprintln!("failed to get cache path: {e}"),
+    }
+    Ok(())
+}
+
+/// An image given to `swww img`. Decoding it is left for when it is actually needed, since that is
+/// skipped entirely when all of its processed images are cached
+struct Source<'a> {
+    path: &'a Path,
+    input: Input<'a>,
+    /// The image's format, if it is animated
+    animation_format: Option<ImageFormat>,
+    key: Option<cache::Key>,
+    img: Option<RgbaImage>,
+}
+
+impl<'a> Source<'a> {
+    fn open(path: &'a Path, raw: Option<&cli::RawImage>) -> Result<Self, String> {
+        if let Some(raw) = raw {
+            let bytes = match path.to_str() {
+                Some("-") => read_stdin()?,
+                _ => match std::fs::read(path) {
+                    Ok(bytes) => bytes.into(),
+                    Err(e) => return Err(format!("failed to read {path:?}: {e}")),
                 },
+            };
+            return Ok(Self {
+                path,
+                animation_format: None,
+                // raw images are usually generated anew every time, so there is no point in caching
+                key: None,
+                img: Some(decode_raw(&bytes, raw)?),
+                input: Input::Memory(bytes),
+            });
+        }
+
+        let input = match path.to_str() {
+            Some("-") => Input::Memory(read_stdin()?),
+            _ => Input::File(path),
+        };
+        Ok(Self {
+            path,
+            animation_format: animation_format(&input)?,
+            key: match input {
+                Input::File(path) => cache::Key::new(path),
+                Input::Memory(_) => None,
             },
-            outputs.to_owned(),
-        ));
+            input,
+            img: None,
+        })
     }
 
-    Ok((transition, unique_requests))
+    /// The decoded image (or the first frame, for animations)
+    fn img(&mut self) -> Result<&RgbaImage, String> {
+        let img = match self.img.take() {
+            Some(img) => img,
+            None => decode_img(&self.input)?,
+        };
+        Ok(self.img.insert(img))
+    }
 }
 
-#[allow(clippy::type_complexity)]
-fn get_dimensions_and_outputs(
-    requested_outputs: Vec<String>,
-) -> Result<(Vec<(u32, u32)>, Vec<Vec<String>>), String> {
-    let mut outputs: Vec<Vec<String>> = Vec::new();
-    let mut dims: Vec<(u32, u32)> = Vec::new();
-    let mut imgs: Vec<communication::BgImg> = Vec::new();
+/// Where an image's bytes are read from. Stdin can only be read once, so it is kept in memory
+#[derive(Clone)]
+enum Input<'a> {
+    File(&'a Path),
+    Memory(Arc<[u8]>),
+}
 
-    let socket = connect_to_socket(5, 100)?;
-    Request::Query.send(&socket)?;
-    let answer = Answer::receive(socket)?;
-    match answer {
-        Answer::Info(infos) => {
-            for info in infos {
-                if !requested_outputs.is_empty() && !requested_outputs.contains(&info.name) {
-                    continue;
-                }
-                let mut should_add = true;
-                let real_dim = (
-                    info.dim.0 * info.scale_factor as u32,
-                    info.dim.1 * info.scale_factor as u32,
-                );
-                for (i, (dim, img)) in dims.iter().zip(&imgs).enumerate() {
-                    if real_dim == *dim && info.img == *img {
-                        outputs[i].push(info.name.clone());
-                        should_add = false;
-                        break;
-                    }
-                }
+/// The decoders need to read the image more than once, to detect its format and whether it is
+/// animated
+trait ImageReader: BufRead + Seek {}
+impl<T: BufRead + Seek> ImageReader for T {}
+
+impl Input<'_> {
+    fn reader(&self) -> Result<Box<dyn ImageReader>, String> {
+        match self {
+            Self::File(path) => match File::open(path) {
+                Ok(file) => Ok(Box::new(BufReader::new(file))),
+                Err(e) => Err(format!("failed to open image: {e}")),
+            },
+            Self::Memory(bytes) => Ok(Box::new(Cursor::new(Arc::clone(bytes)))),
+        }
+    }
+}
 
-                if should_add {
-                    outputs.push(vec![info.name]);
-                    dims.push(real_dim);
-                    imgs.push(info.img);
-                }
+type AnimationHandle<'scope> = std::thread::ScopedJoinHandle<'scope, Result<(), String>>;
+
+/// Makes the images for `requested_outputs`, all of which share the same options, appending them
+/// to `imgs`. If the image is animated, also spawns a thread to make its animation
+fn make_img_requests<'scope, 'env>(
+    s: &'scope std::thread::Scope<'scope, 'env>,
+    imgs: &mut Vec<(communication::Img, Vec<String>)>,
+    source: &mut Source<'env>,
+    img: &'env cli::Img,
+    infos: &[BgInfo],
+    requested_outputs: &[String],
+    stream: &mut AnimationStream,
+) -> Result<Option<AnimationHandle<'scope>>, String> {
+    let path = source.path;
+    if let ResizeStrategy::Span = img.resize_opts.resize {
+        if source.animation_format.is_some() {
+            eprintln!(
+                "WARNING: animations can not be spanned across outputs. \
+                 Displaying only the first frame of {path:?}"
+            );
+        }
+        imgs.append(&mut make_span_request(
+            path,
+            source.img()?.clone(),
+            &img.resize_opts,
+            &img.adjust_opts,
+            infos,
+            requested_outputs,
+        )?);
+        return Ok(None);
+    }
+
+    let (dims, outputs) = img::dimensions_and_outputs(infos, requested_outputs)?;
+    let resize_opts = make_resize(&img.resize_opts);
+    let entries: Vec<Option<cache::Entry>> = dims
+        .iter()
+        .map(|dim| {
+            let key = source.key.as_ref()?;
+            Some(key.entry(*dim, &resize_opts, &img.adjust_opts))
+        })
+        .collect();
+
+    let animation = source.animation_format.map(|format| {
+        let input = source.input.clone();
+        let first = stream.animations.len();
+        stream
+            .animations
+            .extend(outputs.iter().map(|outputs| StreamedAnimation {
+                outputs: outputs.clone(),
+                sync: img.sync,
+            }));
+        let (dims, entries) = (dims.clone(), entries.clone());
+        let frame_sender = stream.frame_sender.clone();
+        s.spawn(move || {
+            make_animation_request(img, &input, format, &dims, &entries, first, frame_sender)
+        })
+    });
+    imgs.append(&mut make_img_request(
+        source,
+        &resize_opts,
+        &img.adjust_opts,
+        &dims,
+        &outputs,
+        &entries,
+    )?);
+    Ok(animation)
+}
+
+/// Asks the daemon to display the next wallpaper in every output's history, only moving through
+/// the history once it did
+fn step_history(
+    client: &Client,
+    restore: &cli::Restore,
+    direction: Direction,
+) -> Result<(), String> {
+    let mut outputs = split_cmdline_outputs(&restore.outputs);
+    if outputs.is_empty() {
+        outputs = client.query()?.into_iter().map(|info| info.name).collect();
+    }
+
+    let cache_path = get_cache_path()?;
+    let mut stepping = Vec::with_capacity(outputs.len());
+    let mut files = Vec::with_capacity(outputs.len());
+    for output in outputs {
+        match history::peek(&cache_path, &output, direction)? {
+            Some(file) => {
+                stepping.push(output);
+                files.push(file);
             }
-            if outputs.is_empty() {
-                Err("none of the requested outputs are valid".to_owned())
-            } else {
-                Ok((dims, outputs))
+            None => eprintln!("WARNING: output {output} has no more wallpapers in its history"),
+        }
+    }
+    if stepping.is_empty() {
+        return Err("none of the outputs have more wallpapers in their history".to_string());
+    }
+
+    client.request(&Request::Restore(communication::Restore {
+        outputs: stepping.clone(),
+        files,
+        transition: make_transition(&restore.transition_opts),
+    }))?;
+    for output in stepping {
+        history::step(&cache_path, &output, direction)?;
+    }
+    Ok(())
+}
+
+fn manage_cache(command: &cli::Cache) -> Result<(), String> {
+    match command {
+        cli::Cache::List => {
+            let listings = cache::list()?;
+            for listing in &listings {
+                println!("{listing}");
             }
+            let size = listings.iter().map(|listing| listing.size).sum();
+            println!(
+                "{} entries, {} in total",
+                listings.len(),
+                cache::format_size(size)
+            );
+        }
+        cli::Cache::Clear => cache::clear()?,
+        cli::Cache::Prune(prune) => {
+            let deleted = cache::prune(prune.max_size)?;
+            println!("deleted {deleted} entries");
         }
-        Answer::Err(e) => Err(format!("failed to query swww-daemon: {e}")),
-        _ => unreachable!(),
     }
+    Ok(())
 }
 
-fn make_animation_request(
-    img: &cli::Img,
-    dims: &[(u32, u32)],
-    outputs: &[Vec<String>],
-) -> Result<AnimationRequest, String> {
-    let filter = make_filter(&img.filter);
-    let mut animations = Vec::with_capacity(dims.len());
-    for (dim, outputs) in dims.iter().zip(outputs) {
-        let imgbuf = match image::io::Reader::open(&img.path) {
-            Ok(img) => img.into_inner(),
-            Err(e) => return Err(format!("error opening image during animation: {e}")),
-        };
-        let gif = match GifDecoder::new(imgbuf) {
-            Ok(gif) => gif,
-            Err(e) => return Err(format!("failed to decode gif during animation: {e}")),
-        };
-        animations.push((
-            communication::Animation {
-                animation: compress_frames(gif, *dim, filter, img.resize, &img.fill_color)?
-                    .into_boxed_slice(),
-                sync: img.sync,
-            },
-            outputs.to_owned(),
-        ));
+/// Keeps the processed images within the same limit `swww cache prune` would use
+fn prune_cache() {
+    // parsing no arguments still picks up SWWW_CACHE_MAX_SIZE
+    match cli::CachePrune::try_parse_from(["prune"]) {
+        Ok(prune) => {
+            if let Err(e) = cache::prune(prune.max_size) {
+                eprintln!("WARNING: failed to prune the cache: {e}");
+            }
+        }
+        Err(e) => eprintln!("WARNING: not pruning the cache: {e}"),
     }
-    Ok(animations)
 }
 
-fn compress_frames(
-    gif: GifDecoder<BufReader<File>>,
-    dim: (u32, u32),
-    filter: FilterType,
-    resize: ResizeStrategy,
-    color: &[u8; 3],
-) -> Result<Vec<(BitPack, Duration)>, String> {
-    let mut compressed_frames = Vec::new();
-    let mut frames = gif.into_frames();
-
-    // The first frame should always exist
-    let first = frames.next().unwrap().unwrap();
-    let first_duration = first.delay().numer_denom_ms();
-    let first_duration = Duration::from_millis((first_duration.0 / first_duration.1).into());
-    let first_img = match resize {
-        ResizeStrategy::No => img_pad(first.into_buffer(), dim, color)?,
-        ResizeStrategy::Crop => img_resize_crop(first.into_buffer(), dim, filter)?,
-        ResizeStrategy::Fit => img_resize_fit(first.into_buffer(), dim, filter, color)?,
+fn make_playlist_request(playlist: &cli::Playlist) -> Result<Request, String> {
+    let (command, target) = match playlist {
+        cli::Playlist::Start(start) => {
+            let mut paths = Vec::with_capacity(start.paths.len());
+            for path in &start.paths {
+                // the daemon runs in a different working directory, so we must send absolute paths
+                match path.canonicalize() {
+                    Ok(p) => paths.push(p),
+                    Err(e) => return Err(format!("failed to canonicalize path {path:?}: {e}")),
+                }
+            }
+            return Ok(Request::Playlist(communication::Playlist {
+                paths,
+                interval: Duration::from_secs(start.interval.into()),
+                order: match start.order {
+                    cli::PlaylistOrder::Sequential => communication::PlaylistOrder::Sequential,
+                    cli::PlaylistOrder::Shuffle => communication::PlaylistOrder::Shuffle,
+                },
+                resize: make_resize(&start.resize_opts),
+                transition: make_transition(&start.transition_opts),
+                outputs: split_cmdline_outputs(&start.outputs),
+            }));
+        }
+        cli::Playlist::Next(target) => (communication::PlaylistCommand::Next, target),
+        cli::Playlist::Prev(target) => (communication::PlaylistCommand::Prev, target),
+        cli::Playlist::Pause(target) => (communication::PlaylistCommand::Pause, target),
+        cli::Playlist::Resume(target) => (communication::PlaylistCommand::Resume, target),
+        cli::Playlist::Stop(target) => (communication::PlaylistCommand::Stop, target),
     };
+    Ok(Request::PlaylistControl(communication::PlaylistControl {
+        command,
+        outputs: split_cmdline_outputs(&target.outputs),
+    }))
+}
 
-    let mut canvas = first_img.clone();
-    while let Some(Ok(frame)) = frames.next() {
-        let (dur_num, dur_div) = frame.delay().numer_denom_ms();
-        let duration = Duration::from_millis((dur_num / dur_div).into());
+fn split_cmdline_outputs(outputs: &str) -> Vec<String> {
+    outputs
+        .split(',')
+        .map(|s| s.to_owned())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
 
-        let img = match resize {
-            ResizeStrategy::No => img_pad(frame.into_buffer(), dim, color)?,
-            ResizeStrategy::Crop => img_resize_crop(frame.into_buffer(), dim, filter)?,
-            ResizeStrategy::Fit => img_resize_fit(frame.into_buffer(), dim, filter, color)?,
-        };
+fn read_stdin() -> Result<Arc<[u8]>, String> {
+    let mut reader = BufReader::new(stdin());
+    let mut buffer = Vec::new();
+    match reader.read_to_end(&mut buffer) {
+        Ok(_) => Ok(buffer.into()),
+        Err(e) => Err(format!("failed to read stdin: {e}")),
+    }
+}
 
-        compressed_frames.push((BitPack::pack(&mut canvas, &img)?, duration));
+/// Turns `--raw` pixels into an image
+fn decode_raw(bytes: &[u8], raw: &cli::RawImage) -> Result<RgbaImage, String> {
+    let channels = match raw.format {
+        cli::RawFormat::Rgba | cli::RawFormat::Bgra => 4,
+        cli::RawFormat::Rgb => 3,
+    };
+    let expected = match (raw.width as usize)
+        .checked_mul(raw.height as usize)
+        .and_then(|pixels| pixels.checked_mul(channels))
+    {
+        Some(expected) => expected,
+        None => return Err(format!("a {}x{} image is too large", raw.width, raw.height)),
+    };
+    if bytes.len() != expected {
+        return Err(format!(
+            "a {}x{} {:?} image must have {expected} bytes, but got {}",
+            raw.width,
+            raw.height,
+            raw.format,
+            bytes.len()
+        ));
     }
-    //Add the first frame we got earlier:
-    compressed_frames.push((BitPack::pack(&mut canvas, &first_img)?, first_duration));
 
-    Ok(compressed_frames)
+    let pixels = match raw.format {
+        cli::RawFormat::Rgba => bytes.to_vec(),
+        cli::RawFormat::Rgb => bytes
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        cli::RawFormat::Bgra => bytes
+            .chunks_exact(4)
+            .flat_map(|p| [p[2], p[1], p[0], p[3]])
+            .collect(),
+    };
+    match RgbaImage::from_raw(raw.width, raw.height, pixels) {
+        Some(img) => Ok(img),
+        None => Err("failed to create image from raw pixels".to_string()),
+    }
 }
 
-fn make_filter(filter: &cli::Filter) -> fast_image_resize::FilterType {
-    match filter {
-        cli::Filter::Nearest => fast_image_resize::FilterType::Box,
-        cli::Filter::Bilinear => fast_image_resize::FilterType::Bilinear,
-        cli::Filter::CatmullRom => fast_image_resize::FilterType::CatmullRom,
-        cli::Filter::Mitchell => fast_image_resize::FilterType::Mitchell,
-        cli::Filter::Lanczos3 => fast_image_resize::FilterType::Lanczos3,
+/// Opens the image, guessing its format from its contents
+fn open_img(input: &Input) -> Result<image::io::Reader<Box<dyn ImageReader>>, String> {
+    match image::io::Reader::new(input.reader()?).with_guessed_format() {
+        Ok(img) => Ok(img),
+        Err(e) => Err(format!("failed to detect the image's format: {e}")),
     }
 }
 
-fn img_pad(
-    mut img: image::RgbaImage,
-    dimensions: (u32, u32),
-    color: &[u8; 3],
-) -> Result<Vec<u8>, String> {
-    let (padded_w, padded_h) = dimensions;
-    let (padded_w, padded_h) = (padded_w as usize, padded_h as usize);
-    let mut padded = Vec::with_capacity(padded_w * padded_w * 4);
+/// Returns the image's format if it is animated. This only reads the image's header
+fn animation_format(input: &Input) -> Result<Option<ImageFormat>, String> {
+    match open_img(input)?.format() {
+        Some(format) if is_animated(input, format)? => Ok(Some(format)),
+        _ => Ok(None),
+    }
+}
 
-    let img = image::imageops::crop(&mut img, 0, 0, dimensions.0, dimensions.1).to_image();
-    let (img_w, img_h) = img.dimensions();
-    let (img_w, img_h) = (img_w as usize, img_h as usize);
-    let raw_img = img.into_vec();
+/// Returns the decoded image (or the first frame, for animations)
+fn decode_img(input: &Input) -> Result<RgbaImage, String> {
+    match open_img(input)?.decode() {
+        Ok(img) => Ok(img.into_rgba8()),
+        Err(e) => Err(format!("failed to decode image: {e}")),
+    }
+}
 
-    for _ in 0..(((padded_h - img_h) / 2) * padded_w) {
-        padded.push(color[2]);
-        padded.push(color[1]);
-        padded.push(color[0]);
-        padded.push(255);
+fn is_animated(input: &Input, format: ImageFormat) -> Result<bool, String> {
+    let reader = input.reader()?;
+    match format {
+        ImageFormat::Gif => Ok(true),
+        ImageFormat::Png => match PngDecoder::new(reader) {
+            Ok(png) => Ok(png.is_apng()),
+            Err(e) => Err(format!("failed to decode png: {e}")),
+        },
+        ImageFormat::WebP => match WebPDecoder::new(reader) {
+            Ok(webp) => Ok(webp.has_animation()),
+            Err(e) => Err(format!("failed to decode webp: {e}")),
+        },
+        _ => Ok(false),
     }
+}
 
-    // Calculate left and right border widths. `u32::div` rounds toward 0, so, if `img_w` is odd,
-    // add an extra pixel to the right border to ensure the row is the correct width.
-    let left_border_w = (padded_w - img_w) / 2;
-    let right_border_w = left_border_w + (img_w % 2);
+/// Resizes and adjusts the image for every one of `dims`, unless it is already in `entries`
+fn make_img_request(
+    source: &mut Source,
+    resize_opts: &communication::Resize,
+    adjust_opts: &cli::AdjustOpts,
+    dims: &[(u32, u32)],
+    outputs: &[Vec<String>],
+    entries: &[Option<cache::Entry>],
+) -> Result<Vec<(communication::Img, Vec<String>)>, String> {
+    let mut unique_requests = Vec::with_capacity(dims.len());
+    for ((dim, outputs), entry) in dims.iter().zip(outputs).zip(entries) {
+        let img = match entry.as_ref().and_then(cache::Entry::load_img) {
+            Some(img) => img,
+            None => {
+                let img = resize(source.img()?.clone(), *dim, resize_opts)?;
+                let img = adjust(img, *dim, adjust_opts)?;
+                if let Some(entry) = entry {
+                    entry.store_img(&img);
+                }
+                img
+            }
+        };
+        unique_requests.push((
+            communication::Img {
+                img,
+                path: img::canonicalize_img_path(source.path)?,
+                span: None,
+            },
+            outputs.to_owned(),
+        ));
+    }
 
-    for row in 0..img_h {
-        for _ in 0..left_border_w {
-            padded.push(color[2]);
-            padded.push(color[1]);
-            padded.push(color[0]);
-            padded.push(255);
-        }
+    Ok(unique_requests)
+}
 
-        for pixel in raw_img[(row * img_w * 4)..((row + 1) * img_w * 4)].chunks_exact(4) {
-            padded.push(pixel[2]);
-            padded.push(pixel[1]);
-            padded.push(pixel[0]);
-            padded.push(pixel[3]);
-        }
-        for _ in 0..right_border_w {
-            padded.push(color[2]);
-            padded.push(color[1]);
-            padded.push(color[0]);
-            padded.push(255);
+/// Cuts the image into one part for each requested output. See `utils::resize::span`
+fn make_span_request(
+    path: &Path,
+    img_raw: image::RgbaImage,
+    resize_opts: &cli::ResizeOpts,
+    adjust_opts: &cli::AdjustOpts,
+    infos: &[BgInfo],
+    requested_outputs: &[String],
+) -> Result<Vec<(communication::Img, Vec<String>)>, String> {
+    let resize_opts = make_resize(resize_opts);
+    let adjusted = has_adjustments(adjust_opts);
+    let img_raw = if adjusted {
+        eprintln!(
+            "WARNING: adjusted images are not spanned again when the outputs are moved or \
+             reconfigured"
+        );
+        adjust_rgba(img_raw, adjust_opts)?
+    } else {
+        img_raw
+    };
+    let mut imgs = img::span_request(path, img_raw, &resize_opts, infos, requested_outputs)?;
+    if adjusted {
+        for (img, _) in imgs.iter_mut() {
+            // the daemon would span the image again without the adjustments
+            img.span = None;
         }
     }
+    Ok(imgs)
+}
 
-    while padded.len() < (padded_h * padded_w * 4) {
-        padded.push(color[2]);
-        padded.push(color[1]);
-        padded.push(color[0]);
-        padded.push(255);
+/// Gets the config file loaded by the daemon. If it failed to load, we go on without it, unless
+/// the user is relying on it for a preset
+fn query_config(client: &Client, needed: bool) -> Result<Config, String> {
+    match client.request(&Request::Config) {
+        Ok(Answer::Config(config)) => Ok(config),
+        Err(swww_client::Error::Daemon(e)) if !needed => {
+            eprintln!("WARNING: {e}. Ignoring the config file");
+            Ok(Config::default())
+        }
+        Err(e) => Err(e.to_string()),
+        Ok(_) => Err(swww_client::Error::UnexpectedAnswer.to_string()),
     }
-
-    Ok(padded)
 }
 
-/// Convert an ARGB &[u8] to BRGA in-place by swapping bytes
-fn argb_to_brga(argb: &mut [u8]) {
-    for pixel in argb.chunks_exact_mut(4) {
-        pixel.swap(0, 2);
+/// Pairs every image with the outputs it was requested to be displayed at.
+///
+/// Images assigned with 'OUTPUT=PATH' go to their outputs. The image without outputs (there can be
+/// only one) goes to the outputs in `--outputs`, or to all the outputs that are left, if that
+/// wasn't set.
+fn assign_outputs<'a>(
+    img: &'a cli::Img,
+    infos: &[BgInfo],
+) -> Result<Vec<(&'a Path, Vec<String>)>, String> {
+    let mut assignments = Vec::with_capacity(img.images.len());
+    let mut assigned: Vec<&str> = Vec::new();
+    let mut unassigned = None;
+    for image in &img.images {
+        if image.outputs.is_empty() {
+            if unassigned.is_some() {
+                return Err(
+                    "only one image may be given without assigning it to outputs (use OUTPUT=PATH)"
+                        .to_string(),
+                );
+            }
+            unassigned = Some(image.path.as_path());
+            continue;
+        }
+        for output in &image.outputs {
+            if !infos.iter().any(|info| &info.name == output) {
+                return Err(format!("output {output} does not exist"));
+            }
+            if assigned.contains(&output.as_str()) {
+                return Err(format!("output {output} was assigned more than one image"));
+            }
+            assigned.push(output);
+        }
+        assignments.push((image.path.as_path(), image.outputs.clone()));
     }
-}
 
-/// Resize an image to fit within the given dimensions, covering as much space as possible without
-/// cropping.
-fn img_resize_fit(
-    img: image::RgbaImage,
-    dimensions: (u32, u32),
-    filter: FilterType,
-    padding_color: &[u8; 3],
-) -> Result<Vec<u8>, String> {
-    let (width, height) = dimensions;
-    let (img_w, img_h) = img.dimensions();
-    if (img_w, img_h) != (width, height) {
-        // if our image is already scaled to fit, skip resizing it and just pad it directly
-        if img_w == width || img_h == height {
-            return img_pad(img, dimensions, padding_color);
+    let requested_outputs = split_cmdline_outputs(&img.outputs);
+    match unassigned {
+        Some(path) => {
+            let outputs = if requested_outputs.is_empty() {
+                let outputs: Vec<String> = infos
+                    .iter()
+                    .filter(|info| !assigned.contains(&info.name.as_str()))
+                    .map(|info| info.name.clone())
+                    .collect();
+                if outputs.is_empty() {
+                    return Err(format!("there are no outputs left to display {path:?} at"));
+                }
+                outputs
+            } else {
+                if let Some(output) = requested_outputs
+                    .iter()
+                    .find(|output| assigned.contains(&output.as_str()))
+                {
+                    return Err(format!("output {output} was assigned more than one image"));
+                }
+                requested_outputs
+            };
+            assignments.push((path, outputs));
         }
+        None if !requested_outputs.is_empty() => {
+            return Err(
+                "--outputs only applies to an image that wasn't assigned outputs with OUTPUT=PATH"
+                    .to_string(),
+            );
+        }
+        None => (),
+    }
 
-        let (trg_w, trg_h) = if width.abs_diff(img_w) > height.abs_diff(img_h) {
-            let scale = height as f32 / img_h as f32;
-            ((img_w as f32 * scale) as u32, height)
-        } else {
-            let scale = width as f32 / img_w as f32;
-            (width, (img_h as f32 * scale) as u32)
-        };
+    if assignments
+        .iter()
+        .filter(|(path, _)| path.to_str() == Some("-"))
+        .count()
+        > 1
+    {
+        return Err("stdin can only be read once".to_string());
+    }
+    Ok(assignments)
+}
 
-        let mut src = match fast_image_resize::Image::from_vec_u8(
-            // We unwrap below because we know the images's dimensions should never be 0
-            NonZeroU32::new(img_w).unwrap(),
-            NonZeroU32::new(img_h).unwrap(),
-            img.into_raw(),
-            PixelType::U8x4,
-        ) {
-            Ok(i) => i,
-            Err(e) => return Err(e.to_string()),
+/// Makes the animation for every one of `dims`, unless it is already in `entries`, sending every
+/// frame through `frame_sender` as soon as it is ready. `first` is the index of the animation for
+/// `dims[0]` in the `Request::AnimationStream`
+fn make_animation_request(
+    img: &cli::Img,
+    input: &Input,
+    format: ImageFormat,
+    dims: &[(u32, u32)],
+    entries: &[Option<cache::Entry>],
+    first: usize,
+    frame_sender: mpsc::Sender<AnimationFrame>,
+) -> Result<(), String> {
+    let resize_opts = make_resize(&img.resize_opts);
+    for (i, (dim, entry)) in dims.iter().zip(entries).enumerate() {
+        let animation = first + i;
+        let send = |(frame, duration): &(BitPack, Duration)| {
+            // the receiver is only gone if the request already failed
+            let _ = frame_sender.send(AnimationFrame::Frame {
+                animation,
+                frame: frame.clone(),
+                duration: *duration,
+            });
         };
-
-        let alpha_mul_div = fast_image_resize::MulDiv::default();
-        if let Err(e) = alpha_mul_div.multiply_alpha_inplace(&mut src.view_mut()) {
-            return Err(e.to_string());
+        match entry.as_ref().and_then(cache::Entry::load_animation) {
+            Some(frames) => frames.iter().for_each(send),
+            None => {
+                let frames =
+                    decode_animation(input, format, *dim, &resize_opts, &img.adjust_opts, send)?;
+                if let Some(entry) = entry {
+                    entry.store_animation(&frames);
+                }
+            }
         }
+        let _ = frame_sender.send(AnimationFrame::End(animation));
+    }
+    Ok(())
+}
 
-        // We unwrap below because we know the outputs's dimensions should never be 0
-        let new_w = NonZeroU32::new(trg_w).unwrap();
-        let new_h = NonZeroU32::new(trg_h).unwrap();
+fn decode_animation(
+    input: &Input,
+    format: ImageFormat,
+    dim: (u32, u32),
+    resize_opts: &communication::Resize,
+    adjust_opts: &cli::AdjustOpts,
+    on_frame: impl FnMut(&(BitPack, Duration)) + Send,
+) -> Result<Vec<(BitPack, Duration)>, String> {
+    let imgbuf = input.reader()?;
+    match format {
+        ImageFormat::Gif => match GifDecoder::new(imgbuf) {
+            Ok(gif) => compress_frames(gif.into_frames(), dim, resize_opts, adjust_opts, on_frame),
+            Err(e) => Err(format!("failed to decode gif during animation: {e}")),
+        },
+        ImageFormat::Png => match PngDecoder::new(imgbuf) {
+            Ok(png) => compress_frames(
+                png.apng().into_frames(),
+                dim,
+                resize_opts,
+                adjust_opts,
+                on_frame,
+            ),
+            Err(e) => Err(format!("failed to decode png during animation: {e}")),
+        },
+        ImageFormat::WebP => match WebPDecoder::new(imgbuf) {
+            Ok(webp) => {
+                compress_frames(webp.into_frames(), dim, resize_opts, adjust_opts, on_frame)
+            }
+            Err(e) => Err(format!("failed to decode webp during animation: {e}")),
+        },
+        _ => Err(format!("{format:?} images can not be animated")),
+    }
+}
 
-        let mut dst = fast_image_resize::Image::new(new_w, new_h, src.pixel_type());
-        let mut dst_view = dst.view_mut();
+/// How many consecutive frames each of `compress_frames`' workers takes at a time. Every chunk but
+/// the first also resizes the frame before it, so smaller chunks waste more work
+const FRAMES_PER_CHUNK: usize = 8;
+
+/// Consecutive frames of an animation, which one of `compress_frames`' workers resizes and packs
+struct Chunk {
+    index: usize,
+    /// The frame right before the chunk, which its first frame is diffed against. `None` for the
+    /// first chunk
+    prev: Option<RgbaImage>,
+    frames: Vec<(RgbaImage, Duration)>,
+}
 
-        let mut resizer = Resizer::new(fast_image_resize::ResizeAlg::Convolution(filter));
-        if let Err(e) = resizer.resize(&src.view(), &mut dst_view) {
-            return Err(e.to_string());
-        }
+struct CompressedChunk {
+    index: usize,
+    frames: Vec<(BitPack, Duration)>,
+    /// The first frame of the animation, resized. Only set for the first chunk, since it is packed
+    /// last, against the last frame
+    first: Option<(Vec<u8>, Duration)>,
+    /// The last frame of the chunk, resized
+    last: Vec<u8>,
+}
 
-        if let Err(e) = alpha_mul_div.divide_alpha_inplace(&mut dst_view) {
-            return Err(e.to_string());
+/// Resizes and packs every frame. Frames are decoded in this thread and handed out in chunks to a
+/// pool of workers, one for every core, so large animations use the whole machine.
+///
+/// `on_frame` is called with every frame, in order, as soon as it is ready.
+fn compress_frames(
+    mut frames: image::Frames,
+    dim: (u32, u32),
+    resize_opts: &communication::Resize,
+    adjust_opts: &cli::AdjustOpts,
+    on_frame: impl FnMut(&(BitPack, Duration)) + Send,
+) -> Result<Vec<(BitPack, Duration)>, String> {
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+    // a rendezvous channel, so we only decode frames as fast as the workers can take them
+    let (chunk_sender, chunk_receiver) = mpsc::sync_channel::<Chunk>(0);
+    // only the workers hold the receiver, so that once they all stopped, like when a chunk failed
+    // to compress, sending us more chunks fails instead of blocking forever
+    let chunk_receiver = Arc::new(Mutex::new(chunk_receiver));
+    let (result_sender, result_receiver) = mpsc::channel();
+
+    std::thread::scope(|s| {
+        for _ in 0..workers {
+            let chunk_receiver = Arc::clone(&chunk_receiver);
+            let result_sender = result_sender.clone();
+            s.spawn(move || loop {
+                let chunk = match chunk_receiver.lock() {
+                    Ok(receiver) => receiver.recv(),
+                    Err(_) => return,
+                };
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    // every frame was decoded
+                    Err(_) => return,
+                };
+                let result = compress_chunk(chunk, dim, resize_opts, adjust_opts);
+                if result_sender.send(result).is_err() {
+                    return;
+                }
+            });
         }
-
-        img_pad(
-            image::RgbaImage::from_raw(trg_w, trg_h, dst.into_vec()).unwrap(),
-            dimensions,
-            padding_color,
-        )
-    } else {
-        let mut res = img.into_vec();
-        // The ARGB is 'little endian', so here we must  put the order
-        // of bytes 'in reverse', so it needs to be BGRA.
-        argb_to_brga(&mut res);
-        Ok(res)
-    }
-}
-
-fn img_resize_crop(
-    img: image::RgbaImage,
-    dimensions: (u32, u32),
-    filter: FilterType,
-) -> Result<Vec<u8>, String> {
-    let (width, height) = dimensions;
-    let (img_w, img_h) = img.dimensions();
-    let mut resized_img = if (img_w, img_h) != (width, height) {
-        let mut src = match fast_image_resize::Image::from_vec_u8(
-            // We unwrap below because we know the images's dimensions should never be 0
-            NonZeroU32::new(img_w).unwrap(),
-            NonZeroU32::new(img_h).unwrap(),
-            img.into_raw(),
-            PixelType::U8x4,
-        ) {
-            Ok(i) => i,
-            Err(e) => return Err(e.to_string()),
+        drop(chunk_receiver);
+        drop(result_sender);
+        let collector = s.spawn(move || collect_chunks(result_receiver, on_frame));
+
+        let mut chunk = match frames.next() {
+            Some(Ok(frame)) => Chunk {
+                index: 0,
+                prev: None,
+                frames: vec![frame_and_duration(frame)],
+            },
+            Some(Err(e)) => return Err(format!("failed to decode first frame: {e}")),
+            None => return Err("animation has no frames".to_string()),
         };
-
-        let alpha_mul_div = fast_image_resize::MulDiv::default();
-        if let Err(e) = alpha_mul_div.multiply_alpha_inplace(&mut src.view_mut()) {
-            return Err(e.to_string());
+        while let Some(Ok(frame)) = frames.next() {
+            if chunk.frames.len() == FRAMES_PER_CHUNK {
+                let next = Chunk {
+                    index: chunk.index + 1,
+                    prev: chunk.frames.last().map(|(img, _)| img.clone()),
+                    frames: Vec::with_capacity(FRAMES_PER_CHUNK),
+                };
+                if chunk_sender
+                    .send(std::mem::replace(&mut chunk, next))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            chunk.frames.push(frame_and_duration(frame));
         }
+        let _ = chunk_sender.send(chunk);
+        // dropping the sender tells the workers we are done
+        drop(chunk_sender);
 
-        // We unwrap below because we know the outputs's dimensions should never be 0
-        let new_w = NonZeroU32::new(width).unwrap();
-        let new_h = NonZeroU32::new(height).unwrap();
-        let mut src_view = src.view();
-        src_view.set_crop_box_to_fit_dst_size(new_w, new_h, Some((0.5, 0.5)));
-
-        let mut dst = fast_image_resize::Image::new(new_w, new_h, PixelType::U8x4);
-        let mut dst_view = dst.view_mut();
-
-        let mut resizer = Resizer::new(fast_image_resize::ResizeAlg::Convolution(filter));
-        if let Err(e) = resizer.resize(&src_view, &mut dst_view) {
-            return Err(e.to_string());
+        match collector.join() {
+            Ok(result) => result,
+            Err(e) => Err(format!("failed to compress frames: {e:?}")),
         }
+    })
+}
 
-        if let Err(e) = alpha_mul_div.divide_alpha_inplace(&mut dst_view) {
-            return Err(e.to_string());
+/// Puts the workers' chunks back in order, passing every frame to `on_frame` as soon as all the
+/// ones before it are ready
+fn collect_chunks(
+    results: mpsc::Receiver<Result<CompressedChunk, String>>,
+    mut on_frame: impl FnMut(&(BitPack, Duration)),
+) -> Result<Vec<(BitPack, Duration)>, String> {
+    let mut compressed_frames = Vec::new();
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+    let mut first = None;
+    let mut canvas = Vec::new();
+    for result in results {
+        let chunk = result?;
+        pending.insert(chunk.index, chunk);
+        while let Some(chunk) = pending.remove(&next) {
+            chunk.frames.iter().for_each(&mut on_frame);
+            compressed_frames.extend(chunk.frames);
+            first = first.or(chunk.first);
+            canvas = chunk.last;
+            next += 1;
         }
+    }
 
-        dst.into_vec()
-    } else {
-        img.into_vec()
+    //Add the first frame we got earlier:
+    let (first_img, first_duration) = match first {
+        Some(first) => first,
+        None => return Err("failed to compress first frame".to_string()),
     };
+    let last = (BitPack::pack(&mut canvas, &first_img)?, first_duration);
+    on_frame(&last);
+    compressed_frames.push(last);
+
+    Ok(compressed_frames)
+}
 
-    // The ARGB is 'little endian', so here we must  put the order
-    // of bytes 'in reverse', so it needs to be BGRA.
-    argb_to_brga(&mut resized_img);
+fn compress_chunk(
+    chunk: Chunk,
+    dim: (u32, u32),
+    resize_opts: &communication::Resize,
+    adjust_opts: &cli::AdjustOpts,
+) -> Result<CompressedChunk, String> {
+    let resize_and_adjust = |img| adjust(resize(img, dim, resize_opts)?, dim, adjust_opts);
+
+    let mut frames = chunk.frames.into_iter();
+    let (mut canvas, first) = match chunk.prev {
+        Some(prev) => (resize_and_adjust(prev)?, None),
+        None => match frames.next() {
+            Some((img, duration)) => {
+                let img = resize_and_adjust(img)?;
+                (img.clone(), Some((img, duration)))
+            }
+            None => return Err("animation has no frames".to_string()),
+        },
+    };
 
-    Ok(resized_img)
+    let mut compressed_frames = Vec::with_capacity(frames.len());
+    for (img, duration) in frames {
+        let img = resize_and_adjust(img)?;
+        compressed_frames.push((BitPack::pack(&mut canvas, &img)?, duration));
+    }
+
+    Ok(CompressedChunk {
+        index: chunk.index,
+        frames: compressed_frames,
+        first,
+        last: canvas,
+    })
+}
+
+fn frame_and_duration(frame: image::Frame) -> (RgbaImage, Duration) {
+    let (dur_num, dur_div) = frame.delay().numer_denom_ms();
+    let duration = Duration::from_millis((dur_num / dur_div).into());
+    (frame.into_buffer(), duration)
 }
 
-fn make_transition(img: &cli::Img) -> communication::Transition {
-    let mut angle = img.transition_angle;
+fn make_resize(opts: &cli::ResizeOpts) -> communication::Resize {
+    communication::Resize {
+        strategy: match opts.resize {
+            ResizeStrategy::No => communication::ResizeStrategy::No,
+            ResizeStrategy::Crop => communication::ResizeStrategy::Crop,
+            ResizeStrategy::Fit => communication::ResizeStrategy::Fit,
+            ResizeStrategy::Stretch => communication::ResizeStrategy::Stretch,
+            ResizeStrategy::Tile => communication::ResizeStrategy::Tile,
+            ResizeStrategy::Integer => communication::ResizeStrategy::Integer,
+            ResizeStrategy::Span => communication::ResizeStrategy::Span { bezel: opts.bezel },
+        },
+        filter: match opts.filter {
+            cli::Filter::Nearest => communication::Filter::Nearest,
+            cli::Filter::Bilinear => communication::Filter::Bilinear,
+            cli::Filter::CatmullRom => communication::Filter::CatmullRom,
+            cli::Filter::Mitchell => communication::Filter::Mitchell,
+            cli::Filter::Lanczos3 => communication::Filter::Lanczos3,
+        },
+        fill_color: opts.fill_color,
+        align: make_position(&opts.align),
+        offset: opts.offset,
+        zoom: opts.zoom,
+    }
+}
 
-    let x = match img.transition_pos.x {
+fn make_position(pos: &cli::CliPosition) -> Position {
+    let x = match pos.x {
         cli::CliCoord::Percent(x) => {
             if !(0.0..=1.0).contains(&x) {
                 println!(
@@ -530,7 +1147,7 @@ fn make_transition(img: &cli::Img) -> communication::Transition {
         cli::CliCoord::Pixel(x) => Coord::Pixel(x),
     };
 
-    let y = match img.transition_pos.y {
+    let y = match pos.y {
         cli::CliCoord::Percent(y) => {
             if !(0.0..=1.0).contains(&y) {
                 println!(
@@ -542,10 +1159,17 @@ fn make_transition(img: &cli::Img) -> communication::Transition {
         cli::CliCoord::Pixel(y) => Coord::Pixel(y),
     };
 
-    let mut pos = Position::new(x, y);
+    Position::new(x, y)
+}
+
+fn make_transition(opts: &cli::TransitionOpts) -> communication::Transition {
+    let mut angle = opts.transition_angle;
+
+    let mut pos = make_position(&opts.transition_pos);
 
-    let transition_type = match img.transition_type {
+    let transition_type = match opts.transition_type {
         cli::TransitionType::Simple => communication::TransitionType::Simple,
+        cli::TransitionType::Fade => communication::TransitionType::Fade,
         cli::TransitionType::Wipe => communication::TransitionType::Wipe,
         cli::TransitionType::Outer => communication::TransitionType::Outer,
         cli::TransitionType::Grow => communication::TransitionType::Grow,
@@ -598,14 +1222,14 @@ fn make_transition(img: &cli::Img) -> communication::Transition {
     };
 
     communication::Transition {
-        duration: img.transition_duration,
-        step: img.transition_step,
-        fps: img.transition_fps,
-        bezier: img.transition_bezier,
+        duration: opts.transition_duration,
+        step: opts.transition_step,
+        fps: opts.transition_fps,
+        bezier: opts.transition_bezier,
         angle,
         pos,
         transition_type,
-        wave: img.transition_wave,
+        wave: opts.transition_wave,
     }
 }
 
@@ -628,61 +1252,127 @@ fn spawn_daemon(no_daemon: bool) -> Result<(), String> {
     }
 }
 
-/// We make sure the Stream is always set to blocking mode
-///
-/// * `tries` -  how make times to attempt the connection
-/// * `interval` - how long to wait between attempts, in milliseconds
-fn connect_to_socket(tries: u8, interval: u64) -> Result<UnixStream, String> {
-    //Make sure we try at least once
-    let tries = if tries == 0 { 1 } else { tries };
-    let path = get_socket_path();
-    let mut error = None;
-    for _ in 0..tries {
-        match UnixStream::connect(&path) {
-            Ok(socket) => {
-                if let Err(e) = socket.set_nonblocking(false) {
-                    return Err(format!("Failed to set blocking connection: {e}"));
-                }
-                return Ok(socket);
-            }
-            Err(e) => error = Some(e),
-        }
-        std::thread::sleep(Duration::from_millis(interval));
-    }
-    let error = error.unwrap();
-    if error.kind() == std::io::ErrorKind::NotFound {
-        return Err("Socket file not found. Are you sure swww-daemon is running?".to_string());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Delay, Frame, Frames};
+
+    fn animation(len: usize) -> Vec<Frame> {
+        (0..len)
+            .map(|i| {
+                let img = RgbaImage::from_fn(4, 2, |x, y| {
+                    image::Rgba([(i * 10) as u8, x as u8, (y * i as u32) as u8, 255])
+                });
+                Frame::from_parts(img, 0, 0, Delay::from_numer_denom_ms(i as u32 + 1, 1))
+            })
+            .collect()
     }
 
-    Err(format!("Failed to connect to socket: {error}"))
-}
+    /// Packs every frame one after another, like we did before using workers
+    fn compress_sequentially(
+        frames: Vec<Frame>,
+        resize_opts: &communication::Resize,
+        adjust_opts: &cli::AdjustOpts,
+    ) -> Vec<(BitPack, Duration)> {
+        let mut imgs = frames.into_iter().map(|frame| {
+            let (img, duration) = frame_and_duration(frame);
+            let img = resize(img, (4, 2), resize_opts).unwrap();
+            (adjust(img, (4, 2), adjust_opts).unwrap(), duration)
+        });
+        let (first, first_duration) = imgs.next().unwrap();
+        let mut canvas = first.clone();
+        let mut compressed = Vec::new();
+        for (img, duration) in imgs {
+            compressed.push((BitPack::pack(&mut canvas, &img).unwrap(), duration));
+        }
+        compressed.push((BitPack::pack(&mut canvas, &first).unwrap(), first_duration));
+        compressed
+    }
 
-fn is_daemon_running() -> Result<bool, String> {
-    let proc = PathBuf::from("/proc");
+    #[test]
+    fn compressing_in_parallel_should_keep_frame_order() {
+        let resize_opts = make_resize(&cli::Img::parse_from(["img", "a.gif"]).resize_opts);
+        let adjust_opts = cli::Img::parse_from(["img", "a.gif", "--brightness", "0.5"]).adjust_opts;
+        for len in [
+            1,
+            2,
+            FRAMES_PER_CHUNK,
+            FRAMES_PER_CHUNK + 1,
+            FRAMES_PER_CHUNK * 3 + 5,
+        ] {
+            let frames = Frames::new(Box::new(animation(len).into_iter().map(Ok)));
+            let parallel =
+                compress_frames(frames, (4, 2), &resize_opts, &adjust_opts, |_| ()).unwrap();
+            let sequential = compress_sequentially(animation(len), &resize_opts, &adjust_opts);
+            assert_eq!(
+                bincode::serialize(&parallel).unwrap(),
+                bincode::serialize(&sequential).unwrap(),
+                "wrong frames for an animation with {len} frames"
+            );
+        }
+    }
 
-    let entries = match proc.read_dir() {
-        Ok(e) => e,
-        Err(e) => return Err(e.to_string()),
-    };
+    #[test]
+    fn should_animate_images_in_memory() {
+        let mut gif = Vec::new();
+        image::codecs::gif::GifEncoder::new(&mut gif)
+            .encode_frames(animation(3))
+            .unwrap();
+        let input = Input::Memory(gif.into());
+        assert_eq!(animation_format(&input).unwrap(), Some(ImageFormat::Gif));
+
+        let resize_opts = make_resize(&cli::Img::parse_from(["img", "-"]).resize_opts);
+        let adjust_opts = cli::Img::parse_from(["img", "-"]).adjust_opts;
+        let mut streamed = 0;
+        let frames = decode_animation(
+            &input,
+            ImageFormat::Gif,
+            (4, 2),
+            &resize_opts,
+            &adjust_opts,
+            |_| streamed += 1,
+        )
+        .unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(streamed, 3);
+    }
 
-    for entry in entries.flatten() {
-        let dirname = entry.file_name();
-        if let Ok(pid) = dirname.to_string_lossy().parse::<u32>() {
-            if std::process::id() == pid {
-                continue;
-            }
-            let mut entry_path = entry.path();
-            entry_path.push("cmdline");
-            if let Ok(cmd) = std::fs::read_to_string(entry_path) {
-                let mut args = cmd.split(&[' ', '\0']);
-                if let Some(arg0) = args.next() {
-                    if arg0.ends_with("swww-daemon") {
-                        return Ok(true);
-                    }
-                }
-            }
-        }
+    #[test]
+    fn should_decode_raw_pixels() {
+        let raw = |format| cli::RawImage {
+            width: 2,
+            height: 1,
+            format,
+        };
+        let rgba = RgbaImage::from_raw(2, 1, vec![1, 2, 3, 255, 4, 5, 6, 255]).unwrap();
+        assert_eq!(
+            decode_raw(&[1, 2, 3, 4, 5, 6], &raw(cli::RawFormat::Rgb)).unwrap(),
+            rgba
+        );
+        assert_eq!(
+            decode_raw(&[3, 2, 1, 255, 6, 5, 4, 255], &raw(cli::RawFormat::Bgra)).unwrap(),
+            rgba
+        );
+        assert!(
+            decode_raw(&[1, 2, 3, 4, 5, 6], &raw(cli::RawFormat::Rgba)).is_err(),
+            "accepting too few bytes"
+        );
+        let huge = cli::RawImage {
+            width: u32::MAX,
+            height: u32::MAX,
+            format: cli::RawFormat::Rgba,
+        };
+        assert!(
+            decode_raw(&[], &huge).is_err(),
+            "accepting overflowing size"
+        );
     }
 
-    Ok(false)
+    #[test]
+    fn playlist_interval_should_not_be_zero() {
+        let start =
+            |interval| cli::PlaylistStart::try_parse_from(["start", "a.png", "-i", interval]);
+        assert!(start("0").is_err());
+        assert_eq!(start("1").unwrap().interval, 1);
+    }
 }
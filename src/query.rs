@@ -0,0 +1,204 @@
+//! Machine readable output for `swww query` and `swww subscribe`
+
+use serde_json::{json, Map, Value};
+use utils::communication::{BgImg, BgInfo, Event, Field, WALLPAPER_FIELDS};
+
+/// Formats all outputs as a JSON array, with one object per output
+pub fn to_json(infos: &[BgInfo]) -> String {
+    let outputs: Vec<Value> = infos
+        .iter()
+        .map(|info| {
+            let mut output = wallpaper_fields(&info.img);
+            for (name, field) in info.fields() {
+                output.insert(name.to_string(), value(field));
+            }
+            Value::Object(output)
+        })
+        .collect();
+    Value::Array(outputs).to_string()
+}
+
+/// Formats the event as a single JSON object
+pub fn event_to_json(event: &Event) -> String {
+    let (event, output) = match event {
+        Event::OutputAdded(output) => ("output-added", output),
+        Event::OutputRemoved(output) => ("output-removed", output),
+        Event::OutputReconfigured(info) => {
+            let real_dim = info.real_dim();
+            let value = json!({
+                "event": "output-reconfigured",
+                "output": info.name,
+                "width": info.dim.0,
+                "height": info.dim.1,
+                "real_width": real_dim.0,
+                "real_height": real_dim.1,
+                "scale": info.scale_factor,
+                "x": info.position.0,
+                "y": info.position.1,
+            });
+            return value.to_string();
+        }
+        Event::WallpaperChanged { output, img } => {
+            let mut value = wallpaper_fields(img);
+            value.insert("event".to_string(), json!("wallpaper-changed"));
+            value.insert("output".to_string(), json!(output));
+            return Value::Object(value).to_string();
+        }
+        Event::TransitionStarted(output) => ("transition-started", output),
+        Event::TransitionFinished(output) => ("transition-finished", output),
+        Event::AnimationStarted(output) => ("animation-started", output),
+        Event::AnimationStopped(output) => ("animation-stopped", output),
+    };
+    json!({ "event": event, "output": output }).to_string()
+}
+
+/// The image, color and gradient being displayed, only one of which isn't null
+fn wallpaper_fields(img: &BgImg) -> Map<String, Value> {
+    let mut fields: Map<String, Value> = WALLPAPER_FIELDS
+        .iter()
+        .map(|name| (name.to_string(), Value::Null))
+        .collect();
+    let (name, field) = img.field();
+    fields.insert(name.to_string(), value(field));
+    fields
+}
+
+fn value(field: Field) -> Value {
+    match field {
+        Field::Text(text) => Value::from(text),
+        Field::Unsigned(n) => Value::from(n),
+        Field::Signed(n) => Value::from(n),
+        Field::Bool(b) => Value::from(b),
+        Field::Colors(colors) => Value::from(colors),
+    }
+}
+
+/// Replaces the placeholders in `format` with the output's information. See `swww query --help`
+pub fn format(format: &str, info: &BgInfo) -> Result<String, String> {
+    let mut formatted = String::with_capacity(format.len());
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('{') if placeholder.is_empty() => {
+                            formatted.push('{');
+                            break;
+                        }
+                        Some('}') => {
+                            formatted.push_str(&placeholder_value(&placeholder, info)?);
+                            break;
+                        }
+                        Some(c) => placeholder.push(c),
+                        None => return Err(format!("unclosed placeholder in format: {format}")),
+                    }
+                }
+            }
+            '}' => match chars.next() {
+                Some('}') => formatted.push('}'),
+                _ => return Err(format!("unmatched '}}' in format: {format}")),
+            },
+            c => formatted.push(c),
+        }
+    }
+    Ok(formatted)
+}
+
+fn placeholder_value(placeholder: &str, info: &BgInfo) -> Result<String, String> {
+    match info
+        .fields()
+        .into_iter()
+        .find(|(name, _)| *name == placeholder)
+    {
+        Some((_, field)) => Ok(field.to_string()),
+        // the output displays something else
+        None if WALLPAPER_FIELDS.contains(&placeholder) => Ok(String::new()),
+        None => Err(format!("unknown placeholder in format: {{{placeholder}}}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn info() -> BgInfo {
+        BgInfo {
+            name: "DP-1".to_string(),
+            dim: (1920, 1080),
+            position: (-1920, 0),
+            scale_factor: 2,
+            img: BgImg::Color([1, 2, 255]),
+            animating: false,
+            transitioning: true,
+        }
+    }
+
+    #[test]
+    fn should_replace_placeholders() {
+        let formatted = format(
+            "{name}: {width}x{height} ({real_width}x{real_height}) at {x},{y} #{color}{image}",
+            &info(),
+        )
+        .unwrap();
+        assert_eq!(formatted, "DP-1: 1920x1080 (3840x2160) at -1920,0 #0102ff");
+
+        let mut info = info();
+        info.img = BgImg::Img(PathBuf::from("/a.png"));
+        let formatted = format("{{{image}}} {transitioning}", &info).unwrap();
+        assert_eq!(formatted, "{/a.png} true");
+    }
+
+    #[test]
+    fn events_should_name_their_output() {
+        let event = |event| serde_json::from_str::<Value>(&event_to_json(&event)).unwrap();
+
+        let value = event(Event::TransitionStarted("DP-1".to_string()));
+        assert_eq!(
+            value,
+            json!({"event": "transition-started", "output": "DP-1"})
+        );
+
+        let value = event(Event::WallpaperChanged {
+            output: "DP-1".to_string(),
+            img: BgImg::Img(PathBuf::from("/a.png")),
+        });
+        assert_eq!(value["event"], "wallpaper-changed");
+        assert_eq!(value["output"], "DP-1");
+        assert_eq!(value["image"], "/a.png");
+        assert!(value["color"].is_null());
+
+        let value = event(Event::OutputReconfigured(info()));
+        assert_eq!(value["output"], "DP-1");
+        assert_eq!(value["real_width"], 3840);
+        assert_eq!(value["x"], -1920);
+    }
+
+    #[test]
+    fn should_reject_bad_formats() {
+        assert!(
+            format("{nme}", &info()).is_err(),
+            "accepting unknown placeholder"
+        );
+        assert!(
+            format("{name", &info()).is_err(),
+            "accepting unclosed placeholder"
+        );
+        assert!(
+            format("name}", &info()).is_err(),
+            "accepting unmatched '}}'"
+        );
+    }
+
+    #[test]
+    fn should_make_valid_json() {
+        let json: Value = serde_json::from_str(&to_json(&[info()])).unwrap();
+        assert_eq!(json[0]["name"], "DP-1");
+        assert_eq!(json[0]["real_width"], 3840);
+        assert_eq!(json[0]["color"], "0102ff");
+        assert_eq!(json[0]["image"], Value::Null);
+        assert_eq!(json[0]["transitioning"], true);
+    }
+}
@@ -64,10 +64,16 @@ fn general_commands() {
     sending_img_with_filter_that_does_not_exist();
     sending_img_from_stdin();
     let output = query_outputs();
+    querying_in_other_formats(&output);
     sending_img_to_individual_monitors(&output);
     sending_img_to_monitor_that_does_not_exist();
+    sending_imgs_assigned_to_monitors(&output);
+    sending_img_spanned();
     sending_img_with_custom_transition();
+    running_playlist();
     clear_outputs();
+    restoring_and_going_through_history();
+    managing_the_cache();
     killing_daemon();
     cmd().arg("query").assert().failure(); //daemon is dead, so this should fail
 }
@@ -99,6 +105,32 @@ fn sending_img_that_does_not_exist() {
     cmd().arg("img").arg("I don't exist").assert().failure();
 }
 
+fn querying_in_other_formats(output: &str) {
+    let json = cmd().arg("query").arg("--json").output().unwrap();
+    assert!(json.status.success());
+    assert!(String::from_utf8(json.stdout)
+        .unwrap()
+        .contains(&format!("\"name\":\"{output}\"")));
+
+    let formatted = cmd()
+        .arg("query")
+        .arg("--format")
+        .arg("{name}")
+        .output()
+        .unwrap();
+    assert!(formatted.status.success());
+    assert!(String::from_utf8(formatted.stdout)
+        .unwrap()
+        .lines()
+        .any(|line| line == output));
+    cmd()
+        .arg("query")
+        .arg("--format")
+        .arg("{not a key}")
+        .assert()
+        .failure();
+}
+
 fn query_outputs() -> String {
     let output = cmd().arg("query").output().expect("Query failed!");
     let stdout = String::from_utf8(output.stdout).unwrap();
@@ -125,6 +157,37 @@ fn sending_img_to_monitor_that_does_not_exist() {
         .failure();
 }
 
+fn sending_imgs_assigned_to_monitors(output: &str) {
+    cmd()
+        .arg("img")
+        .arg(format!("{output}={}", TEST_IMGS[1]))
+        .assert()
+        .success();
+    cmd()
+        .arg("img")
+        .arg(format!("{output}={}", TEST_IMGS[1]))
+        .arg(format!("{output}={}", TEST_IMGS[2]))
+        .assert()
+        .failure();
+    cmd()
+        .arg("img")
+        .arg(format!("AHOY={}", TEST_IMGS[1]))
+        .assert()
+        .failure();
+}
+
+fn sending_img_spanned() {
+    cmd()
+        .arg("img")
+        .arg(TEST_IMGS[0])
+        .arg("--resize")
+        .arg("span")
+        .arg("--bezel")
+        .arg("20")
+        .assert()
+        .success();
+}
+
 fn sending_imgs_with_filter() {
     for filter in ["Nearest", "Bilinear", "CatmullRom", "Mitchell", "Lanczos3"] {
         cmd()
@@ -167,10 +230,59 @@ fn sending_img_with_custom_transition() {
         .success();
 }
 
+fn running_playlist() {
+    cmd()
+        .arg("playlist")
+        .arg("start")
+        .args(TEST_IMGS)
+        .arg("--interval")
+        .arg("1")
+        .arg("--order")
+        .arg("shuffle")
+        .assert()
+        .success();
+    for command in ["next", "prev", "pause", "resume", "stop"] {
+        cmd().arg("playlist").arg(command).assert().success();
+    }
+    // there are no playlists left to stop
+    cmd().arg("playlist").arg("stop").assert().failure();
+}
+
 fn clear_outputs() {
     cmd().arg("clear").assert().success();
 }
 
+fn restoring_and_going_through_history() {
+    cmd().arg("restore").assert().success();
+    cmd().arg("back").assert().success();
+    cmd().arg("forward").assert().success();
+    cmd()
+        .arg("restore")
+        .arg("--outputs")
+        .arg("I don't exist")
+        .assert()
+        .failure();
+}
+
+fn managing_the_cache() {
+    cmd().arg("cache").arg("list").assert().success();
+    cmd()
+        .arg("cache")
+        .arg("prune")
+        .arg("--max-size")
+        .arg("1M")
+        .assert()
+        .success();
+    cmd()
+        .arg("cache")
+        .arg("prune")
+        .arg("--max-size")
+        .arg("a lot")
+        .assert()
+        .failure();
+    cmd().arg("cache").arg("clear").assert().success();
+}
+
 fn killing_daemon() {
     cmd().arg("kill").assert().success();
 }
@@ -2,13 +2,13 @@ use serde::{Deserialize, Serialize};
 use std::{
     fmt,
     fs::File,
-    io::{BufReader, BufWriter},
+    io::{BufReader, BufWriter, Read, Write},
     os::unix::net::UnixStream,
     path::{Path, PathBuf},
     time::Duration,
 };
 
-use crate::comp_decomp::BitPack;
+use crate::{comp_decomp::BitPack, config::Config};
 
 #[derive(PartialEq, Clone, Serialize, Deserialize, Debug)]
 pub enum Coord {
@@ -56,16 +56,73 @@ impl Position {
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
 pub enum BgImg {
     Color([u8; 3]),
+    Gradient(Gradient),
     Img(PathBuf),
 }
 
+/// Formats the color as rrggbb
+#[must_use]
+pub fn hex(color: &[u8; 3]) -> String {
+    format!("{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+/// The value of one of the fields describing an output. See `BgInfo::fields`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Field {
+    Text(String),
+    Unsigned(u32),
+    Signed(i32),
+    Bool(bool),
+    /// Colors as rrggbb
+    Colors(Vec<String>),
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Field::Text(text) => write!(f, "{text}"),
+            Field::Unsigned(n) => write!(f, "{n}"),
+            Field::Signed(n) => write!(f, "{n}"),
+            Field::Bool(b) => write!(f, "{b}"),
+            Field::Colors(colors) => write!(f, "{}", colors.join(",")),
+        }
+    }
+}
+
+/// The names `BgImg::field` can give its field. Outputs only have one of them
+pub const WALLPAPER_FIELDS: [&str; 3] = ["image", "color", "gradient"];
+
+impl BgImg {
+    /// What is being displayed: either the `image`'s path, the `color`, or the `gradient`'s
+    /// colors
+    #[must_use]
+    pub fn field(&self) -> (&'static str, Field) {
+        match self {
+            BgImg::Img(path) => ("image", Field::Text(path.to_string_lossy().into_owned())),
+            BgImg::Color(color) => ("color", Field::Text(hex(color))),
+            BgImg::Gradient(gradient) => (
+                "gradient",
+                Field::Colors(gradient.colors.iter().map(hex).collect()),
+            ),
+        }
+    }
+}
+
 impl fmt::Display for BgImg {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            BgImg::Color(color) => write!(f, "color: {}{}{}", color[0], color[1], color[2]),
+            BgImg::Color(color) => write!(f, "color: {}", hex(color)),
+            BgImg::Gradient(gradient) => {
+                write!(f, "gradient:")?;
+                for (i, color) in gradient.colors.iter().enumerate() {
+                    let separator = if i == 0 { ' ' } else { ',' };
+                    write!(f, "{separator}{}", hex(color))?;
+                }
+                Ok(())
+            }
             BgImg::Img(p) => write!(
                 f,
                 "image: {:#?}",
@@ -79,8 +136,14 @@ impl fmt::Display for BgImg {
 pub struct BgInfo {
     pub name: String,
     pub dim: (u32, u32),
+    /// Position of the output's top-left corner in the compositor's logical space
+    pub position: (i32, i32),
     pub scale_factor: i32,
     pub img: BgImg,
+    /// Whether the output is playing an animation. Only filled in when answering a query
+    pub animating: bool,
+    /// Whether the output is in the middle of a transition. Only filled in when answering a query
+    pub transitioning: bool,
 }
 
 impl BgInfo {
@@ -91,14 +154,40 @@ impl BgInfo {
             self.dim.1 * self.scale_factor as u32,
         )
     }
+
+    /// Everything `swww query --json` and the daemon's D-Bus `Query` tell about the output, by
+    /// name. The last field is what it displays, see `BgImg::field`
+    #[must_use]
+    pub fn fields(&self) -> Vec<(&'static str, Field)> {
+        let real_dim = self.real_dim();
+        vec![
+            ("name", Field::Text(self.name.clone())),
+            ("width", Field::Unsigned(self.dim.0)),
+            ("height", Field::Unsigned(self.dim.1)),
+            ("real_width", Field::Unsigned(real_dim.0)),
+            ("real_height", Field::Unsigned(real_dim.1)),
+            ("scale", Field::Signed(self.scale_factor)),
+            ("x", Field::Signed(self.position.0)),
+            ("y", Field::Signed(self.position.1)),
+            ("animating", Field::Bool(self.animating)),
+            ("transitioning", Field::Bool(self.transitioning)),
+            self.img.field(),
+        ]
+    }
 }
 
 impl fmt::Display for BgInfo {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{}: {}x{}, scale: {}, currently displaying: {}",
-            self.name, self.dim.0, self.dim.1, self.scale_factor, self.img
+            "{}: {}x{}, scale: {}, position: {},{}, currently displaying: {}",
+            self.name,
+            self.dim.0,
+            self.dim.1,
+            self.scale_factor,
+            self.position.0,
+            self.position.1,
+            self.img
         )
     }
 }
@@ -106,6 +195,8 @@ impl fmt::Display for BgInfo {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum TransitionType {
     Simple,
+    /// Crossfades over exactly `Transition::duration`, following `Transition::bezier`
+    Fade,
     Outer,
     Wipe,
     Grow,
@@ -124,16 +215,123 @@ pub struct Transition {
     pub wave: (f32, f32),
 }
 
+/// The same as `swww img`'s defaults
+impl Default for Transition {
+    fn default() -> Self {
+        Self {
+            transition_type: TransitionType::Simple,
+            duration: 3.0,
+            step: 2,
+            fps: 30,
+            angle: 45.0,
+            pos: Position::new(Coord::Percent(0.5), Coord::Percent(0.5)),
+            bezier: (0.54, 0.0, 0.34, 0.99),
+            wave: (20.0, 20.0),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResizeStrategy {
+    No,
+    Crop,
+    Fit,
+    Stretch,
+    /// Repeat the image across the output
+    Tile,
+    /// Scale the image by the largest whole number that fits the output
+    Integer,
+    /// Span the image across all outputs, according to their position. `bezel` is how many
+    /// logical pixels are hidden between two neighbouring outputs
+    Span {
+        bezel: u32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+    CatmullRom,
+    Mitchell,
+    Lanczos3,
+}
+
+/// How to fit an image into an output. See `utils::resize::resize`
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Resize {
+    pub strategy: ResizeStrategy,
+    pub filter: Filter,
+    pub fill_color: [u8; 3],
+    /// Which point of the image is aligned with the same point of the screen
+    pub align: Position,
+    /// In pixels, from the bottom-left, applied after aligning the image
+    pub offset: (i32, i32),
+    /// Multiplies the scale the strategy resized the image to
+    pub zoom: f32,
+}
+
+/// The same as `swww img`'s defaults
+impl Default for Resize {
+    fn default() -> Self {
+        Self {
+            strategy: ResizeStrategy::Crop,
+            filter: Filter::Lanczos3,
+            fill_color: [0, 0, 0],
+            align: Position::new(Coord::Percent(0.5), Coord::Percent(0.5)),
+            offset: (0, 0),
+            zoom: 1.0,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Clear {
     pub color: [u8; 3],
+    /// Drawn instead of `color`, if set
+    pub gradient: Option<Gradient>,
     pub outputs: Vec<String>,
+    pub transition: Transition,
+}
+
+/// A gradient going through all of its colors, which are evenly spaced. See `crate::gradient`
+#[derive(PartialEq, Clone, Serialize, Deserialize, Debug)]
+pub struct Gradient {
+    pub shape: GradientShape,
+    pub colors: Vec<[u8; 3]>,
+}
+
+#[derive(PartialEq, Clone, Serialize, Deserialize, Debug)]
+pub enum GradientShape {
+    /// Along a straight line, at `angle` degrees counter-clockwise from left to right
+    Linear { angle: f32 },
+    /// From `center` to the output's farthest corner
+    Radial { center: Position },
+}
+
+/// Displays the cached wallpapers of `outputs` again. If `outputs` is empty, of all outputs
+#[derive(Serialize, Deserialize)]
+pub struct Restore {
+    pub outputs: Vec<String>,
+    /// The cache file to display at each of `outputs`, in the same order, instead of their current
+    /// ones. Used to display a wallpaper from the history before actually stepping through it
+    pub files: Vec<PathBuf>,
+    pub transition: Transition,
+}
+
+/// Sent along with every part of an image that was spanned across several outputs, so that the
+/// daemon can span it again when they are moved or reconfigured
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Span {
+    pub outputs: Vec<String>,
+    pub resize: Resize,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Img {
     pub path: PathBuf,
     pub img: Vec<u8>,
+    pub span: Option<Span>,
 }
 
 impl TryFrom<&mut BufReader<File>> for Img {
@@ -162,49 +360,186 @@ impl TryFrom<&mut BufReader<File>> for Animation {
     }
 }
 
-pub type AnimationRequest = Vec<(Animation, Vec<String>)>;
+/// One of the animations in a `Request::AnimationStream`
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StreamedAnimation {
+    pub outputs: Vec<String>,
+    pub sync: bool,
+}
+
+/// Sent over the connection of a `Request::AnimationStream`, after the daemon answered it, as
+/// soon as every frame is ready. The frames of each animation must be sent in order
+#[derive(Serialize, Deserialize)]
+pub enum AnimationFrame {
+    /// The next frame of the animation at this index of the request
+    Frame {
+        animation: usize,
+        frame: BitPack,
+        duration: Duration,
+    },
+    /// The animation at this index has no more frames
+    End(usize),
+}
+
+impl AnimationFrame {
+    pub fn send(&self, mut writer: impl Write) -> Result<(), String> {
+        if let Err(e) = bincode::serialize_into(&mut writer, self) {
+            return Err(format!("Failed to send animation frame: {e}"));
+        }
+        match writer.flush() {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("Failed to send animation frame: {e}")),
+        }
+    }
+
+    pub fn receive(reader: impl Read) -> Result<Self, String> {
+        match bincode::deserialize_from(reader) {
+            Ok(frame) => Ok(frame),
+            Err(e) => Err(format!("Failed to receive animation frame: {e}")),
+        }
+    }
+}
+
+/// Sent over the connection of a `Request::Subscribe`, after the daemon answered it, whenever
+/// something happens
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Event {
+    OutputAdded(String),
+    OutputRemoved(String),
+    /// The output changed its dimensions, scale or position
+    OutputReconfigured(BgInfo),
+    WallpaperChanged {
+        output: String,
+        img: BgImg,
+    },
+    TransitionStarted(String),
+    /// Also sent when the transition is interrupted, like by another one
+    TransitionFinished(String),
+    AnimationStarted(String),
+    AnimationStopped(String),
+}
+
+impl Event {
+    pub fn send(&self, mut writer: impl Write) -> Result<(), String> {
+        if let Err(e) = bincode::serialize_into(&mut writer, self) {
+            return Err(format!("Failed to send event: {e}"));
+        }
+        match writer.flush() {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("Failed to send event: {e}")),
+        }
+    }
+
+    pub fn receive(reader: impl Read) -> Result<Self, String> {
+        match bincode::deserialize_from(reader) {
+            Ok(event) => Ok(event),
+            Err(e) => Err(format!("Failed to receive event: {e}")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaylistOrder {
+    Sequential,
+    /// Shuffles the images once per cycle, so no image repeats before all others were shown
+    Shuffle,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Playlist {
+    /// Image files or directories. Directories are scanned again at the start of every cycle
+    pub paths: Vec<PathBuf>,
+    pub interval: Duration,
+    pub order: PlaylistOrder,
+    pub resize: Resize,
+    pub transition: Transition,
+    pub outputs: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaylistCommand {
+    Next,
+    Prev,
+    Pause,
+    Resume,
+    Stop,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlaylistControl {
+    pub command: PlaylistCommand,
+    pub outputs: Vec<String>,
+}
+
 pub type ImageRequest = (Transition, Vec<(Img, Vec<String>)>);
 
+/// Version of the protocol spoken through the socket. Must be bumped whenever a `Request`, an
+/// `Answer`, or anything sent inside them changes
+pub const PROTOCOL_VERSION: u32 = 4;
+
+/// What the daemon answers to `Request::Ping`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Ping {
+    pub protocol: u32,
+    /// Version of the daemon itself, only meant for people to read
+    pub version: String,
+    /// Optional features the daemon was built with
+    pub capabilities: Vec<String>,
+}
+
+/// `Kill` must stay the fourth variant, right before `Ping`, and `Ok` and `Err` the first variants of
+/// `Answer`, where they have been since before pings existed. That way the client can tell when
+/// the daemon speaks another protocol version, and kill it, whatever version it is
 #[derive(Serialize, Deserialize)]
 pub enum Request {
-    Animation(AnimationRequest),
+    /// Starts playing animations while their frames are still being made. Once the daemon answers,
+    /// the connection stays open, and the frames are sent through it as `AnimationFrame`s
+    AnimationStream(Vec<StreamedAnimation>),
     Clear(Clear),
-    Init,
+    /// Asks for the config file the daemon loaded
+    Config,
     Kill,
+    /// Asks for the daemon's protocol version and capabilities. Daemons from before pings existed
+    /// take this for a query
+    Ping,
     Query,
-    Img(ImageRequest),
+    Restore(Restore),
+    /// Once the daemon answers, the connection stays open, and an `Event` is sent through it
+    /// whenever something happens, until either side closes it
+    Subscribe,
+    /// Every group of images has its own transition, since they can differ between outputs. They
+    /// all start together
+    Img(Vec<ImageRequest>),
+    Playlist(Playlist),
+    PlaylistControl(PlaylistControl),
+    /// Only answered once none of the outputs (or no output at all, if empty) is transitioning.
+    /// Use `Answer::receive_eventually` for the answer, since it may take a while
+    Wait(Vec<String>),
 }
 
 impl Request {
     pub fn send(&self, stream: &UnixStream) -> Result<(), String> {
         let writer = BufWriter::new(stream);
-        std::thread::scope(|s| {
-            let serializer = s.spawn(|| match bincode::serialize_into(writer, self) {
-                Ok(()) => Ok(()),
-                Err(e) => Err(format!("Failed to serialize request: {e}")),
-            });
-
-            match self {
-                Request::Animation(animations) => match get_cache_path() {
-                    Ok(cache_path) => {
-                        s.spawn(move || Self::cache_animations(animations, cache_path));
-                    }
-                    Err(e) => eprintln!("failed to get cache path: {e}"),
-                },
-                Request::Img((_, images)) => match get_cache_path() {
-                    Ok(cache_path) => {
-                        s.spawn(move || Self::cache_images(images, cache_path));
+        match bincode::serialize_into(writer, self) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("Failed to serialize request: {e}")),
+        }
+    }
+
+    /// Caches the images of a `Request::Img`, recording the ones they replace in the history. Must
+    /// only be called once the daemon accepted the request, or the cache and the history would no
+    /// longer match what is being displayed
+    pub fn cache_accepted(&self) {
+        if let Request::Img(requests) = self {
+            match get_cache_path() {
+                Ok(cache_path) => {
+                    for (_, images) in requests {
+                        Self::cache_images(images, cache_path.clone(), true);
                     }
-                    Err(e) => eprintln!("failed to get cache path: {e}"),
-                },
-                _ => (),
-            };
-
-            match serializer.join() {
-                Ok(result) => result,
-                Err(e) => Err(format!("{e:?}")),
+                }
+                Err(e) => eprintln!("failed to get cache path: {e}"),
             }
-        })
+        }
     }
 
     pub fn receive(stream: &UnixStream) -> Result<Self, String> {
@@ -215,9 +550,20 @@ impl Request {
         }
     }
 
-    fn cache_images(images: &[(Img, Vec<String>)], mut cache_path: PathBuf) {
+    /// Caches every image for its outputs. With `record_history`, the images being replaced are
+    /// kept in the outputs' history, see `crate::history`
+    pub fn cache_images(
+        images: &[(Img, Vec<String>)],
+        mut cache_path: PathBuf,
+        record_history: bool,
+    ) {
         for (img, outputs) in images {
             for output in outputs {
+                if record_history {
+                    if let Err(e) = crate::history::record(&cache_path, output) {
+                        eprintln!("failed to record history for output {output}: {e}");
+                    }
+                }
                 cache_path.push(output);
                 match File::create(&cache_path) {
                     Ok(file) => {
@@ -235,7 +581,9 @@ impl Request {
         }
     }
 
-    fn cache_animations(animations: &[(Animation, Vec<String>)], mut cache_path: PathBuf) {
+    /// Appends every animation to the cache files of its outputs, right after their image. Must be
+    /// called after `cache_images`
+    pub fn cache_animations(animations: &[(Animation, Vec<String>)], mut cache_path: PathBuf) {
         for (animation, outputs) in animations {
             for output in outputs {
                 cache_path.push(output);
@@ -261,8 +609,13 @@ pub enum Answer {
     Ok,
     Err(String),
     Info(Vec<BgInfo>),
+    Ping(Ping),
+    Config(Config),
 }
 
+/// Where `Answer::Ping` is in `Answer`
+const PING_VARIANT: u32 = 3;
+
 impl Answer {
     pub fn send(&self, stream: &UnixStream) -> Result<(), String> {
         match bincode::serialize_into(stream, self) {
@@ -272,15 +625,28 @@ impl Answer {
     }
 
     pub fn receive(stream: UnixStream) -> Result<Self, String> {
-        #[cfg(debug_assertions)]
-        let timeout = Duration::from_secs(30); //Some operations take a while to respond in debug mode
-        #[cfg(not(debug_assertions))]
-        let timeout = Duration::from_secs(5);
+        set_answer_timeout(&stream)?;
+        Self::receive_eventually(stream)
+    }
 
-        if let Err(e) = stream.set_read_timeout(Some(timeout)) {
-            return Err(format!("Failed to set read timeout: {e}"));
-        };
+    /// Receives the answer to `Request::Ping`. `None` if it was anything else, which is what
+    /// daemons from before pings existed answer, in whatever their protocol version was
+    pub fn receive_ping(stream: UnixStream) -> Result<Option<Ping>, String> {
+        set_answer_timeout(&stream)?;
+        let mut reader = BufReader::new(stream);
+        match bincode::deserialize_from::<_, u32>(&mut reader) {
+            Ok(PING_VARIANT) => (),
+            Ok(_) => return Ok(None),
+            Err(e) => return Err(format!("Failed to receive answer: {e}")),
+        }
+        match bincode::deserialize_from(reader) {
+            Ok(ping) => Ok(Some(ping)),
+            Err(e) => Err(format!("Failed to receive answer: {e}")),
+        }
+    }
 
+    /// Like `receive`, but without a timeout, for answers that only come once something is done
+    pub fn receive_eventually(stream: UnixStream) -> Result<Self, String> {
         match bincode::deserialize_from(stream) {
             Ok(i) => Ok(i),
             Err(e) => Err(format!("Failed to receive answer: {e}")),
@@ -288,19 +654,77 @@ impl Answer {
     }
 }
 
+fn set_answer_timeout(stream: &UnixStream) -> Result<(), String> {
+    #[cfg(debug_assertions)]
+    let timeout = Duration::from_secs(30); //Some operations take a while to respond in debug mode
+    #[cfg(not(debug_assertions))]
+    let timeout = Duration::from_secs(5);
+
+    match stream.set_read_timeout(Some(timeout)) {
+        Ok(()) => Ok(()),
+        Err(e) => Err(format!("Failed to set read timeout: {e}")),
+    }
+}
+
+/// Environment variable holding the namespace. See `get_namespace`
+pub const NAMESPACE_ENV: &str = "SWWW_NAMESPACE";
+
+/// Every namespace has its own socket and cache, so that several daemons can run at once, for
+/// example one per Wayland display. Defaults to the name of the Wayland display we are running in
+#[must_use]
+pub fn get_namespace() -> String {
+    match std::env::var(NAMESPACE_ENV) {
+        Ok(namespace) if !namespace.is_empty() => namespace,
+        _ => {
+            let display =
+                std::env::var("WAYLAND_DISPLAY").unwrap_or_else(|_| "wayland-0".to_string());
+            // the display may also be the absolute path to its socket
+            match Path::new(&display).file_name() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => display,
+            }
+        }
+    }
+}
+
+/// Makes `get_namespace` return `namespace` from now on, in this process and in the ones it spawns.
+/// Must be called before spawning any threads
+pub fn set_namespace(namespace: &str) -> Result<(), String> {
+    if namespace.is_empty() || namespace == "." || namespace == ".." || namespace.contains('/') {
+        return Err(format!(
+            "invalid namespace '{namespace}': it must be a valid file name"
+        ));
+    }
+    std::env::set_var(NAMESPACE_ENV, namespace);
+    Ok(())
+}
+
+/// Whether the user left the namespace to us. Daemons from before namespaces existed can only be
+/// found in the default one
+fn is_default_namespace() -> bool {
+    !std::env::var(NAMESPACE_ENV).is_ok_and(|namespace| !namespace.is_empty())
+}
+
+fn get_runtime_dir() -> PathBuf {
+    match std::env::var("XDG_RUNTIME_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => PathBuf::from("/tmp/swww"),
+    }
+}
+
 #[must_use]
 pub fn get_socket_path() -> PathBuf {
-    let runtime_dir = if let Ok(dir) = std::env::var("XDG_RUNTIME_DIR") {
-        dir
-    } else {
-        "/tmp/swww".to_string()
-    };
-    let runtime_dir = Path::new(&runtime_dir);
-    runtime_dir.join("swww.socket")
+    get_runtime_dir().join(format!("swww-{}.socket", get_namespace()))
+}
+
+/// Where daemons up to swww 0.7.3 listened. `None` outside of the default namespace
+#[must_use]
+pub fn get_legacy_socket_path() -> Option<PathBuf> {
+    is_default_namespace().then(|| get_runtime_dir().join("swww.socket"))
 }
 
 pub fn get_cache_path() -> Result<PathBuf, String> {
-    let cache_path = match std::env::var("XDG_CACHE_HOME") {
+    let mut cache_path = match std::env::var("XDG_CACHE_HOME") {
         Ok(dir) => {
             let mut cache = PathBuf::from(dir);
             cache.push("swww");
@@ -315,15 +739,86 @@ pub fn get_cache_path() -> Result<PathBuf, String> {
             Err(_) => return Err("failed to read both XDG_CACHE_HOME and HOME env vars".to_owned()),
         },
     };
+    cache_path.push(get_namespace());
 
     if !cache_path.is_dir() {
-        if let Err(e) = std::fs::create_dir(&cache_path) {
+        if let Err(e) = std::fs::create_dir_all(&cache_path) {
             return Err(format!(
                 "failed to create cache_path \"{}\": {e}",
                 cache_path.display()
             ));
         }
+        if is_default_namespace() {
+            migrate_legacy_cache(&cache_path);
+        }
     }
 
     Ok(cache_path)
 }
+
+/// Up to swww 0.7.3, the cached wallpapers lived right in the cache directory, which now only has
+/// a directory per namespace. Moves them into the default namespace's, which was just created
+fn migrate_legacy_cache(cache_path: &Path) {
+    let Some(legacy) = cache_path.parent() else {
+        return;
+    };
+    let Ok(entries) = std::fs::read_dir(legacy) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if !entry.file_type().is_ok_and(|t| t.is_file()) {
+            continue;
+        }
+        let to = cache_path.join(entry.file_name());
+        if let Err(e) = std::fs::rename(entry.path(), &to) {
+            eprintln!(
+                "failed to move old cache file {} to {}: {e}",
+                entry.path().display(),
+                to.display()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versioned_variants_should_never_move() {
+        let request = |request| bincode::serialize(&request).unwrap();
+        // where they were in swww 0.7.3, the last version without pings
+        assert_eq!(request(Request::Kill), [3, 0, 0, 0]);
+        assert_eq!(request(Request::Ping), [4, 0, 0, 0]);
+
+        let answer = |answer| bincode::serialize(&answer).unwrap()[..4].to_vec();
+        assert_eq!(answer(Answer::Ok), [0, 0, 0, 0]);
+        assert_eq!(answer(Answer::Err(String::new())), [1, 0, 0, 0]);
+        let ping = Ping {
+            protocol: PROTOCOL_VERSION,
+            version: String::new(),
+            capabilities: Vec::new(),
+        };
+        assert_eq!(answer(Answer::Ping(ping)), PING_VARIANT.to_le_bytes());
+    }
+
+    #[test]
+    fn legacy_cache_should_move_into_the_namespace() {
+        let legacy = std::env::temp_dir().join(format!("swww-legacy-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&legacy);
+        let other = legacy.join("other-namespace");
+        std::fs::create_dir_all(&other).unwrap();
+        std::fs::write(legacy.join("DP-1"), "old").unwrap();
+        let namespace = legacy.join("wayland-1");
+        std::fs::create_dir(&namespace).unwrap();
+
+        migrate_legacy_cache(&namespace);
+        assert!(!legacy.join("DP-1").exists());
+        assert_eq!(
+            std::fs::read_to_string(namespace.join("DP-1")).unwrap(),
+            "old"
+        );
+        assert!(other.is_dir());
+        std::fs::remove_dir_all(&legacy).unwrap();
+    }
+}
@@ -42,7 +42,13 @@ where
         while cur == goal {
             equals += 1;
             match iter.next() {
-                None => return v.into_boxed_slice(),
+                None => {
+                    // `unpack_bytes` expects the trailing 0, unless there were no changes at all
+                    if !v.is_empty() {
+                        v.push(0);
+                    }
+                    return v.into_boxed_slice();
+                }
                 Some((j, (c, g))) => {
                     i = j;
                     cur = c;
@@ -109,7 +115,7 @@ fn unpack_bytes(buf: &mut [u8], diff: &[u8]) {
 }
 
 /// This struct represents the cached difference between the previous frame and the next
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct BitPack {
     inner: Box<[u8]>,
 }
@@ -272,6 +278,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn should_compress_and_decompress_frames_ending_unchanged() {
+        let frame1 = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+        let frame2 = [4, 3, 2, 1, 5, 6, 7, 8, 9, 10, 11, 12];
+        let compressed = BitPack::pack(&mut frame1.clone(), &frame2).unwrap();
+
+        let mut buf = frame1;
+        compressed.ready(12).unpack(&mut buf);
+        for i in 0..3 {
+            assert_eq!(frame2[i * 4..i * 4 + 3], buf[i * 4..i * 4 + 3]);
+        }
+
+        let compressed = BitPack::pack(&mut frame2.clone(), &frame2).unwrap();
+        compressed.ready(12).unpack(&mut buf);
+        for i in 0..3 {
+            assert_eq!(frame2[i * 4..i * 4 + 3], buf[i * 4..i * 4 + 3]);
+        }
+    }
+
     #[test]
     fn should_compress_and_decompress_to_same_info() {
         for _ in 0..10 {
@@ -0,0 +1,215 @@
+//! The configuration file, holding defaults for `swww img`'s options.
+//!
+//! It lives at `$XDG_CONFIG_HOME/swww/config.toml` (or `$HOME/.config/swww/config.toml`), and looks
+//! like this:
+//!
+//! ```toml
+//! [default]
+//! transition-type = "wipe"
+//! transition-fps = 60
+//!
+//! [outputs.DP-1]
+//! resize = "fit"
+//! fill-color = "282828"
+//!
+//! [presets.night]
+//! brightness = 0.6
+//! ```
+//!
+//! Every key is the name of one of `swww img`'s long options, and every value is what would be
+//! given to that option in the command line. Arrays are joined with commas. The daemon loads the
+//! file, and the client asks it for the parsed config, which it applies itself.
+
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// Maps every option's name to its value, exactly as it would be written in the command line
+pub type Section = BTreeMap<String, String>;
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub default: Section,
+    pub outputs: BTreeMap<String, Section>,
+    pub presets: BTreeMap<String, Section>,
+}
+
+impl Config {
+    /// Loads the config file. A missing config file is the same as an empty one
+    pub fn load() -> Result<Self, String> {
+        let path = get_config_path()?;
+        match std::fs::read_to_string(&path) {
+            Ok(raw) => Self::parse(&raw).map_err(|e| format!("{}: {e}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("failed to read {}: {e}", path.display())),
+        }
+    }
+
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let table = match raw.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let mut config = Self::default();
+        for (name, value) in table {
+            match name.as_str() {
+                "default" => config.default = section(&name, value)?,
+                "outputs" => config.outputs = sections(&name, value)?,
+                "presets" => config.presets = sections(&name, value)?,
+                _ => return Err(format!("unknown section: [{name}]")),
+            }
+        }
+        Ok(config)
+    }
+
+    /// The options for `output`. The output's own section overrides the default section, and the
+    /// preset, if any, overrides both
+    pub fn options_for(&self, output: &str, preset: Option<&str>) -> Result<Section, String> {
+        let mut options = self.default.clone();
+        if let Some(section) = self.outputs.get(output) {
+            options.extend(section.clone());
+        }
+        if let Some(preset) = preset {
+            match self.presets.get(preset) {
+                Some(section) => options.extend(section.clone()),
+                None => return Err(format!("preset '{preset}' not found in the config file")),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn sections(name: &str, value: toml::Value) -> Result<BTreeMap<String, Section>, String> {
+    match value {
+        toml::Value::Table(table) => table
+            .into_iter()
+            .map(|(key, value)| {
+                let section = section(&format!("{name}.{key}"), value)?;
+                Ok((key, section))
+            })
+            .collect(),
+        _ => Err(format!("[{name}] is not a table")),
+    }
+}
+
+fn section(name: &str, value: toml::Value) -> Result<Section, String> {
+    match value {
+        toml::Value::Table(table) => table
+            .into_iter()
+            .map(|(key, value)| match to_arg(&value) {
+                Some(arg) => Ok((key, arg)),
+                None => Err(format!("invalid value for '{key}' in [{name}]")),
+            })
+            .collect(),
+        _ => Err(format!("[{name}] is not a table")),
+    }
+}
+
+/// Writes `value` the way it would be written in the command line
+fn to_arg(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(s) => Some(s.clone()),
+        toml::Value::Integer(i) => Some(i.to_string()),
+        // Debug always keeps the decimal point, which matters for options like `--transition-pos`,
+        // where integers are pixels and floats are percentages
+        toml::Value::Float(f) => Some(format!("{f:?}")),
+        toml::Value::Boolean(b) => Some(b.to_string()),
+        toml::Value::Array(values) => values
+            .iter()
+            .map(|value| match value {
+                toml::Value::Array(_) | toml::Value::Table(_) => None,
+                value => to_arg(value),
+            })
+            .collect::<Option<Vec<String>>>()
+            .map(|values| values.join(",")),
+        toml::Value::Datetime(_) | toml::Value::Table(_) => None,
+    }
+}
+
+pub fn get_config_path() -> Result<PathBuf, String> {
+    let mut path = match std::env::var("XDG_CONFIG_HOME") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => match std::env::var("HOME") {
+            Ok(dir) => {
+                let mut config = PathBuf::from(dir);
+                config.push(".config");
+                config
+            }
+            Err(_) => {
+                return Err("failed to read both XDG_CONFIG_HOME and HOME env vars".to_owned())
+            }
+        },
+    };
+    path.push("swww/config.toml");
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [default]
+        transition-type = "wipe"
+        transition-fps = 60
+        transition-pos = [1.0, 0.5]
+        sync = true
+
+        [outputs.DP-1]
+        transition-type = "grow"
+        resize = "fit"
+
+        [presets.night]
+        resize = "crop"
+        brightness = 0.5
+    "#;
+
+    #[test]
+    fn should_parse_values_as_command_line_args() {
+        let config = Config::parse(CONFIG).unwrap();
+        assert_eq!(config.default["transition-type"], "wipe");
+        assert_eq!(config.default["transition-fps"], "60");
+        assert_eq!(config.default["transition-pos"], "1.0,0.5");
+        assert_eq!(config.default["sync"], "true");
+        assert_eq!(config.outputs["DP-1"]["resize"], "fit");
+    }
+
+    #[test]
+    fn should_override_defaults() {
+        let config = Config::parse(CONFIG).unwrap();
+
+        let options = config.options_for("HDMI-A-1", None).unwrap();
+        assert_eq!(options["transition-type"], "wipe");
+        assert!(!options.contains_key("resize"));
+
+        let options = config.options_for("DP-1", None).unwrap();
+        assert_eq!(options["transition-type"], "grow");
+        assert_eq!(options["resize"], "fit");
+        assert_eq!(options["transition-fps"], "60");
+
+        let options = config.options_for("DP-1", Some("night")).unwrap();
+        assert_eq!(options["resize"], "crop");
+        assert_eq!(options["brightness"], "0.5");
+
+        assert!(
+            config.options_for("DP-1", Some("day")).is_err(),
+            "accepting missing preset"
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_configs() {
+        assert!(
+            Config::parse("[defaults]\nresize = \"fit\"").is_err(),
+            "accepting unknown section"
+        );
+        assert!(
+            Config::parse("[default]\nresize = { a = 1 }").is_err(),
+            "accepting nested table as value"
+        );
+        assert!(
+            Config::parse("default = 1").is_err(),
+            "accepting section that isn't a table"
+        );
+    }
+}
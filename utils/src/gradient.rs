@@ -0,0 +1,138 @@
+//! Renders the gradients `swww clear` fills outputs with.
+//!
+//! The colors are evenly spaced along the gradient, and interpolated in sRGB. Since a gradient
+//! across a whole screen changes very slowly between neighbouring pixels, it is dithered with a
+//! Bayer matrix to avoid banding. Like `crate::resize`, the result is in the BGRA format the daemon
+//! draws with.
+
+use crate::communication::{Gradient, GradientShape};
+
+/// Thresholds of the ordered dithering, in sixteenths
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+pub fn render(gradient: &Gradient, dim: (u32, u32)) -> Vec<u8> {
+    let (width, height) = (dim.0 as f32, dim.1 as f32);
+    // `position` gives every pixel's place along the gradient, from 0 to 1
+    let position: Box<dyn Fn(f32, f32) -> f32> = match &gradient.shape {
+        GradientShape::Linear { angle } => {
+            let (sin, cos) = angle.to_radians().sin_cos();
+            // how far the corners are from the center along the gradient's direction
+            let extent = (width * cos.abs() + height * sin.abs()) / 2.0;
+            Box::new(move |x, y| {
+                // y grows upwards, like everywhere else positions are given
+                let along = (x - width / 2.0) * cos + (height / 2.0 - y) * sin;
+                (along + extent) / (2.0 * extent)
+            })
+        }
+        GradientShape::Radial { center } => {
+            let (cx, cy) = center.to_pixel(dim);
+            let cy = height - cy;
+            let radius = [(0.0, 0.0), (width, 0.0), (0.0, height), (width, height)]
+                .iter()
+                .map(|(x, y)| (x - cx).hypot(y - cy))
+                .fold(0.0, f32::max);
+            Box::new(move |x, y| (x - cx).hypot(y - cy) / radius)
+        }
+    };
+
+    let mut canvas = Vec::with_capacity(dim.0 as usize * dim.1 as usize * 4);
+    for y in 0..dim.1 {
+        for x in 0..dim.0 {
+            let [r, g, b] = color_at(&gradient.colors, position(x as f32 + 0.5, y as f32 + 0.5));
+            let threshold = (f32::from(BAYER[y as usize % 4][x as usize % 4]) + 0.5) / 16.0;
+            let dither = |channel: f32| (channel + threshold).floor().clamp(0.0, 255.0) as u8;
+            canvas.extend_from_slice(&[dither(b), dither(g), dither(r), 255]);
+        }
+    }
+    canvas
+}
+
+/// Interpolates the color at `t` along the gradient, without rounding it
+fn color_at(colors: &[[u8; 3]], t: f32) -> [f32; 3] {
+    let last = match colors.len().checked_sub(1) {
+        Some(last) => last,
+        None => return [0.0; 3],
+    };
+    // NaN, from a gradient over an empty output, ends up at the first color
+    let t = t.clamp(0.0, 1.0) * last as f32;
+    let i = (t as usize).min(last.saturating_sub(1));
+    let (from, to) = (colors[i], colors[(i + 1).min(last)]);
+    let frac = t - i as f32;
+    let mix = |c: usize| f32::from(from[c]) + (f32::from(to[c]) - f32::from(from[c])) * frac;
+    [mix(0), mix(1), mix(2)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::communication::{Coord, Position};
+
+    fn pixel(canvas: &[u8], dim: (u32, u32), x: u32, y: u32) -> [u8; 3] {
+        let i = (y * dim.0 + x) as usize * 4;
+        [canvas[i + 2], canvas[i + 1], canvas[i]]
+    }
+
+    #[test]
+    fn linear_gradients_should_follow_the_angle() {
+        let dim = (64, 32);
+        let gradient = |angle| Gradient {
+            shape: GradientShape::Linear { angle },
+            colors: vec![[0, 0, 0], [255, 0, 255]],
+        };
+
+        let canvas = render(&gradient(0.0), dim);
+        assert_eq!(canvas.len(), 64 * 32 * 4);
+        assert!(
+            pixel(&canvas, dim, 0, 10)[0] < 8,
+            "left isn't the first color"
+        );
+        assert!(
+            pixel(&canvas, dim, 63, 10)[0] > 247,
+            "right isn't the last color"
+        );
+        // the dithering repeats every 4 rows
+        assert_eq!(pixel(&canvas, dim, 20, 0), pixel(&canvas, dim, 20, 28));
+
+        let canvas = render(&gradient(90.0), dim);
+        assert!(
+            pixel(&canvas, dim, 10, 31)[2] < 8,
+            "bottom isn't the first color"
+        );
+        assert!(
+            pixel(&canvas, dim, 10, 0)[2] > 247,
+            "top isn't the last color"
+        );
+    }
+
+    #[test]
+    fn radial_gradients_should_start_at_the_center() {
+        let dim = (40, 40);
+        let gradient = Gradient {
+            shape: GradientShape::Radial {
+                center: Position::new(Coord::Percent(0.0), Coord::Percent(1.0)),
+            },
+            colors: vec![[255, 255, 255], [100, 100, 100], [0, 0, 0]],
+        };
+        let canvas = render(&gradient, dim);
+        // the center is at the top-left corner, so the farthest corner is the bottom-right one
+        assert!(pixel(&canvas, dim, 0, 0)[1] > 247);
+        assert!(pixel(&canvas, dim, 39, 39)[1] < 8);
+        let middle = pixel(&canvas, dim, 20, 20)[1];
+        assert!((96..=104).contains(&middle), "middle stop is {middle}");
+    }
+
+    #[test]
+    fn dithering_should_average_to_the_exact_color() {
+        let dim = (4, 4);
+        let gradient = Gradient {
+            shape: GradientShape::Linear { angle: 0.0 },
+            colors: vec![[10, 10, 10], [11, 11, 11]],
+        };
+        // halfway between the two colors, about half of the pixels should round up
+        let canvas = render(&gradient, dim);
+        let sum: u32 = (0..4)
+            .map(|y| u32::from(pixel(&canvas, dim, 1, y)[0] + pixel(&canvas, dim, 2, y)[0]))
+            .sum();
+        assert!((82..=86).contains(&sum), "dithered sum is {sum}");
+    }
+}
@@ -0,0 +1,180 @@
+//! A bounded history of the wallpapers displayed at every output.
+//!
+//! The current wallpaper of every output is cached at `<cache path>/<output>`. Whenever it is
+//! replaced, the old cache file is moved to `<cache path>/history/<output>/back`. Going back moves
+//! the current cache file to `<cache path>/history/<output>/forward` and brings back the newest one
+//! in `back`, while going forward does the opposite. Setting a new wallpaper clears `forward`.
+
+use std::path::{Path, PathBuf};
+
+/// How many wallpapers we remember for each output, in each direction
+pub const HISTORY_LEN: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Back,
+    Forward,
+}
+
+/// Moves `output`'s current cache file into its history. Must be called right before the cache file
+/// is replaced with a new wallpaper
+pub fn record(cache_path: &Path, output: &str) -> Result<(), String> {
+    let current = cache_path.join(output);
+    if !current.is_file() {
+        return Ok(());
+    }
+
+    let history = history_dir(cache_path, output);
+    push(&history.join("back"), &current)?;
+    let forward = history.join("forward");
+    if forward.is_dir() {
+        if let Err(e) = std::fs::remove_dir_all(&forward) {
+            return Err(format!("failed to clear {}: {e}", forward.display()));
+        }
+    }
+    Ok(())
+}
+
+/// The cache file `step` would make `output`'s current one, without changing anything. `None` if
+/// there is nothing to step to
+pub fn peek(
+    cache_path: &Path,
+    output: &str,
+    direction: Direction,
+) -> Result<Option<PathBuf>, String> {
+    let from = stack(cache_path, output, direction);
+    Ok(entries(&from)?.pop().map(|n| from.join(n.to_string())))
+}
+
+/// Replaces `output`'s current cache file with the previous (or next) one in its history.
+///
+/// Returns whether there was anything to step to.
+pub fn step(cache_path: &Path, output: &str, direction: Direction) -> Result<bool, String> {
+    let next = match peek(cache_path, output, direction)? {
+        Some(next) => next,
+        None => return Ok(false),
+    };
+    let to = stack(cache_path, output, direction.reverse());
+    let current = cache_path.join(output);
+    if current.is_file() {
+        push(&to, &current)?;
+    }
+    match std::fs::rename(&next, &current) {
+        Ok(()) => Ok(true),
+        Err(e) => Err(format!("failed to restore {}: {e}", next.display())),
+    }
+}
+
+impl Direction {
+    fn reverse(self) -> Self {
+        match self {
+            Self::Back => Self::Forward,
+            Self::Forward => Self::Back,
+        }
+    }
+}
+
+/// Where the wallpapers we would step to in `direction` are kept
+fn stack(cache_path: &Path, output: &str, direction: Direction) -> PathBuf {
+    history_dir(cache_path, output).join(match direction {
+        Direction::Back => "back",
+        Direction::Forward => "forward",
+    })
+}
+
+fn history_dir(cache_path: &Path, output: &str) -> PathBuf {
+    let mut path = cache_path.join("history");
+    path.push(output);
+    path
+}
+
+/// Moves `file` to the top of the stack in `dir`, dropping the oldest files if it gets too big
+fn push(dir: &Path, file: &Path) -> Result<(), String> {
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        return Err(format!("failed to create {}: {e}", dir.display()));
+    }
+
+    let mut entries = entries(dir)?;
+    let next = entries.last().map_or(0, |n| n + 1);
+    if let Err(e) = std::fs::rename(file, dir.join(next.to_string())) {
+        return Err(format!("failed to move {} to history: {e}", file.display()));
+    }
+
+    entries.push(next);
+    let excess = entries.len().saturating_sub(HISTORY_LEN);
+    for n in &entries[..excess] {
+        if let Err(e) = std::fs::remove_file(dir.join(n.to_string())) {
+            return Err(format!("failed to remove old history entry: {e}"));
+        }
+    }
+    Ok(())
+}
+
+/// The numbers of the files in `dir`, from oldest to newest
+fn entries(dir: &Path) -> Result<Vec<u64>, String> {
+    let read_dir = match std::fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("failed to read {}: {e}", dir.display())),
+    };
+
+    let mut entries: Vec<u64> = read_dir
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect();
+    entries.sort_unstable();
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_wallpaper(cache_path: &Path, output: &str, contents: &str) {
+        record(cache_path, output).unwrap();
+        std::fs::write(cache_path.join(output), contents).unwrap();
+    }
+
+    fn current(cache_path: &Path, output: &str) -> String {
+        std::fs::read_to_string(cache_path.join(output)).unwrap()
+    }
+
+    #[test]
+    fn should_step_back_and_forward() {
+        let cache_path = std::env::temp_dir().join(format!("swww-history-{}", std::process::id()));
+        std::fs::create_dir_all(&cache_path).unwrap();
+
+        set_wallpaper(&cache_path, "DP-1", "a");
+        set_wallpaper(&cache_path, "DP-1", "b");
+        set_wallpaper(&cache_path, "DP-1", "c");
+
+        // peeking doesn't move anything
+        let previous = peek(&cache_path, "DP-1", Direction::Back).unwrap().unwrap();
+        assert_eq!(std::fs::read_to_string(previous).unwrap(), "b");
+        assert_eq!(current(&cache_path, "DP-1"), "c");
+        assert_eq!(peek(&cache_path, "DP-1", Direction::Forward).unwrap(), None);
+
+        assert!(step(&cache_path, "DP-1", Direction::Back).unwrap());
+        assert_eq!(current(&cache_path, "DP-1"), "b");
+        assert!(step(&cache_path, "DP-1", Direction::Back).unwrap());
+        assert_eq!(current(&cache_path, "DP-1"), "a");
+        assert!(!step(&cache_path, "DP-1", Direction::Back).unwrap());
+        assert_eq!(current(&cache_path, "DP-1"), "a");
+
+        assert!(step(&cache_path, "DP-1", Direction::Forward).unwrap());
+        assert_eq!(current(&cache_path, "DP-1"), "b");
+
+        // a new wallpaper forgets everything ahead of us
+        set_wallpaper(&cache_path, "DP-1", "d");
+        assert!(!step(&cache_path, "DP-1", Direction::Forward).unwrap());
+        assert!(step(&cache_path, "DP-1", Direction::Back).unwrap());
+        assert_eq!(current(&cache_path, "DP-1"), "b");
+
+        for i in 0..HISTORY_LEN * 2 {
+            set_wallpaper(&cache_path, "DP-1", &i.to_string());
+        }
+        let back = history_dir(&cache_path, "DP-1").join("back");
+        assert_eq!(entries(&back).unwrap().len(), HISTORY_LEN);
+
+        std::fs::remove_dir_all(&cache_path).unwrap();
+    }
+}
@@ -1,2 +1,6 @@
 pub mod communication;
 pub mod comp_decomp;
+pub mod config;
+pub mod gradient;
+pub mod history;
+pub mod resize;
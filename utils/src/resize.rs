@@ -0,0 +1,510 @@
+//! Functions to fit an image into an output's dimensions.
+//!
+//! These are shared between the client, which processes every image sent with `swww img`, and the
+//! daemon, which has to process images by itself when running a playlist. All of them return the
+//! image in the BGRA format the daemon draws with.
+
+use fast_image_resize::{CropBox, FilterType, PixelType, Resizer};
+use image::RgbaImage;
+use std::num::NonZeroU32;
+
+use crate::communication::{BgInfo, Filter, Resize, ResizeStrategy};
+
+/// Resizes `img` to `dimensions` according to `resize`.
+///
+/// The strategy decides the image's base scale, which is then multiplied by the zoom. The scaled
+/// image is placed according to the alignment and offset, and whatever part of the screen it does
+/// not cover is filled with the fill color (or with more copies of the image, when tiling).
+///
+/// Since there is only one output here, `ResizeStrategy::Span` behaves just like `Crop`. Use `span`
+/// to span an image across several outputs.
+pub fn resize(img: RgbaImage, dimensions: (u32, u32), resize: &Resize) -> Result<Vec<u8>, String> {
+    let (img_w, img_h) = img.dimensions();
+    let (width, height) = dimensions;
+    let (x, y, scale_x, scale_y) = placement(img.dimensions(), dimensions, resize);
+    let scaled_w = f64::from(img_w) * scale_x;
+    let scaled_h = f64::from(img_h) * scale_y;
+
+    if let ResizeStrategy::Tile = resize.strategy {
+        return tile(img, dimensions, (x, y), (scaled_w, scaled_h), resize);
+    }
+
+    let mut canvas = Vec::with_capacity(width as usize * height as usize * 4);
+    for _ in 0..(width as usize * height as usize) {
+        canvas.extend_from_slice(&[
+            resize.fill_color[2],
+            resize.fill_color[1],
+            resize.fill_color[0],
+            255,
+        ]);
+    }
+
+    // The part of the screen covered by the image. Note negative floats saturate to 0 when cast
+    let left = x.round() as u32;
+    let top = y.round() as u32;
+    let right = (x + scaled_w).min(f64::from(width)).round() as u32;
+    let bottom = (y + scaled_h).min(f64::from(height)).round() as u32;
+    if right <= left || bottom <= top {
+        return Ok(canvas);
+    }
+    let (dst_w, dst_h) = (right - left, bottom - top);
+
+    // And the part of the image that covers it
+    let src_left = (((f64::from(left) - x) / scale_x) as u32).min(img_w - 1);
+    let src_top = (((f64::from(top) - y) / scale_y) as u32).min(img_h - 1);
+    let src_w = ((f64::from(dst_w) / scale_x).round() as u32).clamp(1, img_w - src_left);
+    let src_h = ((f64::from(dst_h) / scale_y).round() as u32).clamp(1, img_h - src_top);
+
+    let visible = if (src_w, src_h) == (dst_w, dst_h) {
+        image::imageops::crop_imm(&img, src_left, src_top, src_w, src_h)
+            .to_image()
+            .into_raw()
+    } else {
+        resize_region(
+            img,
+            (src_left, src_top, src_w, src_h),
+            (dst_w, dst_h),
+            make_filter(&resize.filter),
+        )?
+    };
+
+    for (row, line) in visible.chunks_exact(dst_w as usize * 4).enumerate() {
+        let start = ((top as usize + row) * width as usize + left as usize) * 4;
+        let dst = &mut canvas[start..start + line.len()];
+        dst.copy_from_slice(line);
+        // The ARGB is 'little endian', so here we must  put the order
+        // of bytes 'in reverse', so it needs to be BGRA.
+        argb_to_brga(dst);
+    }
+
+    Ok(canvas)
+}
+
+/// Where the image's top-left corner goes in the screen, and by how much it is scaled
+/// horizontally and vertically, as in `(x, y, scale_x, scale_y)`
+fn placement(
+    (img_w, img_h): (u32, u32),
+    dimensions: (u32, u32),
+    resize: &Resize,
+) -> (f64, f64, f64, f64) {
+    let (width, height) = dimensions;
+    let w_ratio = f64::from(width) / f64::from(img_w);
+    let h_ratio = f64::from(height) / f64::from(img_h);
+    let (scale_x, scale_y) = match resize.strategy {
+        ResizeStrategy::No | ResizeStrategy::Tile => (1.0, 1.0),
+        ResizeStrategy::Crop | ResizeStrategy::Span { .. } => {
+            let scale = w_ratio.max(h_ratio);
+            (scale, scale)
+        }
+        ResizeStrategy::Fit => {
+            let scale = w_ratio.min(h_ratio);
+            (scale, scale)
+        }
+        ResizeStrategy::Stretch => (w_ratio, h_ratio),
+        ResizeStrategy::Integer => {
+            let scale = w_ratio.min(h_ratio).floor().max(1.0);
+            (scale, scale)
+        }
+    };
+    let zoom = f64::from(resize.zoom);
+    let (scale_x, scale_y) = (scale_x * zoom, scale_y * zoom);
+
+    let scaled_w = f64::from(img_w) * scale_x;
+    let scaled_h = f64::from(img_h) * scale_y;
+    // Like the transition's position, the alignment and the offset start from the bottom-left
+    let (align_x, align_y) = resize.align.to_percent(dimensions);
+    let x = (f64::from(width) - scaled_w) * f64::from(align_x) + f64::from(resize.offset.0);
+    let y =
+        (f64::from(height) - scaled_h) * (1.0 - f64::from(align_y)) - f64::from(resize.offset.1);
+
+    (x, y, scale_x, scale_y)
+}
+
+/// Repeats `img`, scaled to `tile_dim`, across the whole screen, such that one of the tiles has its
+/// top-left corner at `origin`
+fn tile(
+    img: RgbaImage,
+    dimensions: (u32, u32),
+    origin: (f64, f64),
+    tile_dim: (f64, f64),
+    resize: &Resize,
+) -> Result<Vec<u8>, String> {
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    let tile_w = (tile_dim.0.round() as u32).max(1);
+    let tile_h = (tile_dim.1.round() as u32).max(1);
+
+    let mut tile = if img.dimensions() == (tile_w, tile_h) {
+        img.into_raw()
+    } else {
+        let (img_w, img_h) = img.dimensions();
+        resize_region(
+            img,
+            (0, 0, img_w, img_h),
+            (tile_w, tile_h),
+            make_filter(&resize.filter),
+        )?
+    };
+    argb_to_brga(&mut tile);
+
+    let (tile_w, tile_h) = (tile_w as usize, tile_h as usize);
+    let first_col = (-(origin.0.round() as i64)).rem_euclid(tile_w as i64) as usize;
+    let first_row = (-(origin.1.round() as i64)).rem_euclid(tile_h as i64) as usize;
+
+    let mut canvas = Vec::with_capacity(width * height * 4);
+    for row in 0..height {
+        let tile_row = (first_row + row) % tile_h;
+        let line = &tile[tile_row * tile_w * 4..(tile_row + 1) * tile_w * 4];
+        let mut col = first_col;
+        let mut remaining = width;
+        while remaining > 0 {
+            let len = (tile_w - col).min(remaining);
+            canvas.extend_from_slice(&line[col * 4..(col + len) * 4]);
+            remaining -= len;
+            col = 0;
+        }
+    }
+
+    Ok(canvas)
+}
+
+/// Spans `img` across `outputs`, as if they were a single big screen laid out according to their
+/// positions. The image is scaled to cover that whole screen, and every output gets the part that
+/// falls inside it.
+///
+/// Returns one image for each output, in the same order as `outputs`.
+pub fn span(img: RgbaImage, outputs: &[&BgInfo], resize: &Resize) -> Result<Vec<Vec<u8>>, String> {
+    let bezel = match resize.strategy {
+        ResizeStrategy::Span { bezel } => i64::from(bezel),
+        _ => 0,
+    };
+
+    // Every distinct edge is a seam between columns (or rows) of outputs, and every seam to the left
+    // of (or above) an output moves it away by one bezel. Outputs sharing an edge share the seam
+    let edges = |edge: fn(&BgInfo) -> i64| {
+        let mut edges: Vec<i64> = outputs.iter().map(|o| edge(o)).collect();
+        edges.sort_unstable();
+        edges.dedup();
+        edges
+    };
+    let right_edges = edges(|o| i64::from(o.position.0) + i64::from(o.dim.0));
+    let bottom_edges = edges(|o| i64::from(o.position.1) + i64::from(o.dim.1));
+    let rects: Vec<(i64, i64, i64, i64)> = outputs
+        .iter()
+        .map(|output| {
+            let (x, y) = (i64::from(output.position.0), i64::from(output.position.1));
+            let left = right_edges.iter().filter(|edge| **edge <= x).count() as i64;
+            let above = bottom_edges.iter().filter(|edge| **edge <= y).count() as i64;
+            (
+                x + left * bezel,
+                y + above * bezel,
+                i64::from(output.dim.0),
+                i64::from(output.dim.1),
+            )
+        })
+        .collect();
+
+    let (min_x, min_y) = match (
+        rects.iter().map(|r| r.0).min(),
+        rects.iter().map(|r| r.1).min(),
+    ) {
+        (Some(x), Some(y)) => (x, y),
+        _ => return Err("there are no outputs to span the image across".to_string()),
+    };
+    // the unwraps are fine because we know `rects` isn't empty
+    let canvas_w = rects.iter().map(|r| r.0 + r.2).max().unwrap() - min_x;
+    let canvas_h = rects.iter().map(|r| r.1 + r.3).max().unwrap() - min_y;
+
+    let (img_w, img_h) = img.dimensions();
+    let scale = f64::max(
+        canvas_w as f64 / f64::from(img_w),
+        canvas_h as f64 / f64::from(img_h),
+    );
+    // center the canvas in the scaled image
+    let offset_x = (f64::from(img_w) * scale - canvas_w as f64) / 2.0;
+    let offset_y = (f64::from(img_h) * scale - canvas_h as f64) / 2.0;
+
+    let mut src = match fast_image_resize::Image::from_vec_u8(
+        // We unwrap below because we know the images's dimensions should never be 0
+        NonZeroU32::new(img_w).unwrap(),
+        NonZeroU32::new(img_h).unwrap(),
+        img.into_raw(),
+        PixelType::U8x4,
+    ) {
+        Ok(i) => i,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let alpha_mul_div = fast_image_resize::MulDiv::default();
+    if let Err(e) = alpha_mul_div.multiply_alpha_inplace(&mut src.view_mut()) {
+        return Err(e.to_string());
+    }
+
+    let mut resizer = Resizer::new(fast_image_resize::ResizeAlg::Convolution(make_filter(
+        &resize.filter,
+    )));
+    let mut imgs = Vec::with_capacity(outputs.len());
+    for (rect, output) in rects.iter().zip(outputs) {
+        let (new_w, new_h) = match output.real_dim() {
+            (w, h) if w != 0 && h != 0 => {
+                (NonZeroU32::new(w).unwrap(), NonZeroU32::new(h).unwrap())
+            }
+            _ => {
+                return Err(format!(
+                    "output {} has not been configured yet",
+                    output.name
+                ))
+            }
+        };
+
+        let left = ((((rect.0 - min_x) as f64 + offset_x) / scale) as u32).min(img_w - 1);
+        let top = ((((rect.1 - min_y) as f64 + offset_y) / scale) as u32).min(img_h - 1);
+        let width = ((rect.2 as f64 / scale).round() as u32).clamp(1, img_w - left);
+        let height = ((rect.3 as f64 / scale).round() as u32).clamp(1, img_h - top);
+
+        let mut src_view = src.view();
+        if let Err(e) = src_view.set_crop_box(CropBox {
+            left,
+            top,
+            width: NonZeroU32::new(width).unwrap(),
+            height: NonZeroU32::new(height).unwrap(),
+        }) {
+            return Err(e.to_string());
+        }
+
+        let mut dst = fast_image_resize::Image::new(new_w, new_h, PixelType::U8x4);
+        let mut dst_view = dst.view_mut();
+        if let Err(e) = resizer.resize(&src_view, &mut dst_view) {
+            return Err(e.to_string());
+        }
+
+        if let Err(e) = alpha_mul_div.divide_alpha_inplace(&mut dst_view) {
+            return Err(e.to_string());
+        }
+
+        let mut spanned = dst.into_vec();
+        argb_to_brga(&mut spanned);
+        imgs.push(spanned);
+    }
+
+    Ok(imgs)
+}
+
+fn make_filter(filter: &Filter) -> FilterType {
+    match filter {
+        Filter::Nearest => FilterType::Box,
+        Filter::Bilinear => FilterType::Bilinear,
+        Filter::CatmullRom => FilterType::CatmullRom,
+        Filter::Mitchell => FilterType::Mitchell,
+        Filter::Lanczos3 => FilterType::Lanczos3,
+    }
+}
+
+/// Convert an ARGB &[u8] to BRGA in-place by swapping bytes
+fn argb_to_brga(argb: &mut [u8]) {
+    for pixel in argb.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+}
+
+/// Resizes the `(left, top, width, height)` region of `img` to `dimensions`
+fn resize_region(
+    img: RgbaImage,
+    region: (u32, u32, u32, u32),
+    dimensions: (u32, u32),
+    filter: FilterType,
+) -> Result<Vec<u8>, String> {
+    let (img_w, img_h) = img.dimensions();
+    let mut src = match fast_image_resize::Image::from_vec_u8(
+        // We unwrap below because we know the images's dimensions should never be 0
+        NonZeroU32::new(img_w).unwrap(),
+        NonZeroU32::new(img_h).unwrap(),
+        img.into_raw(),
+        PixelType::U8x4,
+    ) {
+        Ok(i) => i,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let alpha_mul_div = fast_image_resize::MulDiv::default();
+    if let Err(e) = alpha_mul_div.multiply_alpha_inplace(&mut src.view_mut()) {
+        return Err(e.to_string());
+    }
+
+    let mut src_view = src.view();
+    // We unwrap below because our callers never give us empty regions
+    if let Err(e) = src_view.set_crop_box(CropBox {
+        left: region.0,
+        top: region.1,
+        width: NonZeroU32::new(region.2).unwrap(),
+        height: NonZeroU32::new(region.3).unwrap(),
+    }) {
+        return Err(e.to_string());
+    }
+
+    let mut dst = fast_image_resize::Image::new(
+        NonZeroU32::new(dimensions.0).unwrap(),
+        NonZeroU32::new(dimensions.1).unwrap(),
+        PixelType::U8x4,
+    );
+    let mut dst_view = dst.view_mut();
+
+    let mut resizer = Resizer::new(fast_image_resize::ResizeAlg::Convolution(filter));
+    if let Err(e) = resizer.resize(&src_view, &mut dst_view) {
+        return Err(e.to_string());
+    }
+
+    if let Err(e) = alpha_mul_div.divide_alpha_inplace(&mut dst_view) {
+        return Err(e.to_string());
+    }
+
+    Ok(dst.into_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resize as resize_fn;
+    use super::*;
+    use crate::communication::{BgImg, Coord, Position};
+
+    fn output(name: &str, position: (i32, i32), dim: (u32, u32)) -> BgInfo {
+        BgInfo {
+            name: name.to_string(),
+            dim,
+            position,
+            scale_factor: 1,
+            img: BgImg::Color([0, 0, 0]),
+            animating: false,
+            transitioning: false,
+        }
+    }
+
+    /// An image whose every column has a different color
+    fn striped_img(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, _| image::Rgba([x as u8 * 10, 0, 0, 255]))
+    }
+
+    fn resize_opts(strategy: ResizeStrategy) -> Resize {
+        Resize {
+            strategy,
+            filter: Filter::Nearest,
+            fill_color: [1, 1, 1],
+            align: Position::new(Coord::Percent(0.5), Coord::Percent(0.5)),
+            offset: (0, 0),
+            zoom: 1.0,
+        }
+    }
+
+    fn columns(img: &[u8], width: usize) -> Vec<u8> {
+        img.chunks_exact(4)
+            .take(width)
+            .map(|pixel| pixel[2])
+            .collect()
+    }
+
+    #[test]
+    fn span_should_give_each_output_its_part_of_the_image() {
+        let left = output("left", (0, 0), (2, 2));
+        let right = output("right", (2, 0), (2, 2));
+        let resize = resize_opts(ResizeStrategy::Span { bezel: 0 });
+
+        let imgs = span(striped_img(4, 2), &[&right, &left], &resize).unwrap();
+        assert_eq!(columns(&imgs[0], 2), vec![20, 30]);
+        assert_eq!(columns(&imgs[1], 2), vec![0, 10]);
+    }
+
+    #[test]
+    fn span_should_skip_the_bezels() {
+        let left = output("left", (0, 0), (2, 2));
+        let right = output("right", (2, 0), (2, 2));
+        let resize = resize_opts(ResizeStrategy::Span { bezel: 2 });
+
+        let imgs = span(striped_img(6, 2), &[&left, &right], &resize).unwrap();
+        assert_eq!(columns(&imgs[0], 2), vec![0, 10]);
+        assert_eq!(columns(&imgs[1], 2), vec![40, 50]);
+
+        // in a grid, outputs in the same column or row share the same bezels
+        let grid = [
+            output("top-left", (0, 0), (2, 2)),
+            output("top-right", (2, 0), (2, 2)),
+            output("bottom-left", (0, 2), (2, 2)),
+            output("bottom-right", (2, 2), (2, 2)),
+        ];
+        let img = RgbaImage::from_fn(6, 6, |x, y| {
+            image::Rgba([x as u8 * 10, y as u8 * 10, 0, 255])
+        });
+        let imgs = span(img, &grid.iter().collect::<Vec<_>>(), &resize).unwrap();
+        // the red and green of every output's top-left pixel, which are its x and y in the image
+        let corners: Vec<(u8, u8)> = imgs.iter().map(|img| (img[2], img[1])).collect();
+        assert_eq!(corners, vec![(0, 0), (40, 0), (0, 40), (40, 40)]);
+    }
+
+    #[test]
+    fn crop_should_keep_the_aligned_part_of_the_image() {
+        let mut resize = resize_opts(ResizeStrategy::Crop);
+        let img = resize_fn(striped_img(4, 1), (2, 1), &resize).unwrap();
+        assert_eq!(columns(&img, 2), vec![10, 20]);
+
+        resize.align = Position::new(Coord::Percent(0.0), Coord::Percent(0.5));
+        let img = resize_fn(striped_img(4, 1), (2, 1), &resize).unwrap();
+        assert_eq!(columns(&img, 2), vec![0, 10]);
+
+        resize.align = Position::new(Coord::Percent(1.0), Coord::Percent(0.5));
+        let img = resize_fn(striped_img(4, 1), (2, 1), &resize).unwrap();
+        assert_eq!(columns(&img, 2), vec![20, 30]);
+    }
+
+    #[test]
+    fn no_resize_should_apply_the_offset() {
+        let mut resize = resize_opts(ResizeStrategy::No);
+        resize.offset = (1, 0);
+        let img = resize_fn(striped_img(2, 1), (4, 1), &resize).unwrap();
+        assert_eq!(columns(&img, 4), vec![1, 1, 0, 10]);
+
+        // the parts moved outside the screen are cut out
+        resize.offset = (-2, 0);
+        let img = resize_fn(striped_img(2, 1), (4, 1), &resize).unwrap();
+        assert_eq!(columns(&img, 4), vec![10, 1, 1, 1]);
+    }
+
+    #[test]
+    fn fit_should_pad_zoomed_out_images() {
+        let mut resize = resize_opts(ResizeStrategy::Fit);
+        let img = resize_fn(striped_img(2, 2), (4, 2), &resize).unwrap();
+        assert_eq!(columns(&img, 4), vec![1, 0, 10, 1]);
+
+        resize.zoom = 0.5;
+        resize.align = Position::new(Coord::Percent(0.0), Coord::Percent(1.0));
+        let img = resize_fn(striped_img(2, 2), (4, 4), &resize).unwrap();
+        assert_eq!(columns(&img, 4), vec![0, 10, 1, 1]);
+    }
+
+    #[test]
+    fn stretch_should_ignore_the_aspect_ratio() {
+        let resize = resize_opts(ResizeStrategy::Stretch);
+        assert_eq!(placement((2, 4), (4, 2), &resize), (0.0, 0.0, 2.0, 0.5));
+    }
+
+    #[test]
+    fn tile_should_repeat_the_image() {
+        let mut resize = resize_opts(ResizeStrategy::Tile);
+        resize.align = Position::new(Coord::Percent(0.0), Coord::Percent(1.0));
+        let img = resize_fn(striped_img(2, 1), (5, 2), &resize).unwrap();
+        assert_eq!(columns(&img, 5), vec![0, 10, 0, 10, 0]);
+        assert_eq!(columns(&img[5 * 4..], 5), vec![0, 10, 0, 10, 0]);
+
+        resize.offset = (1, 0);
+        let img = resize_fn(striped_img(2, 1), (5, 2), &resize).unwrap();
+        assert_eq!(columns(&img, 5), vec![10, 0, 10, 0, 10]);
+    }
+
+    #[test]
+    fn integer_should_scale_by_whole_numbers() {
+        let resize = resize_opts(ResizeStrategy::Integer);
+        // 5 / 2 = 2.5, so the image should be doubled and centered
+        assert_eq!(placement((2, 1), (5, 2), &resize), (0.5, 0.0, 2.0, 2.0));
+
+        // images larger than the screen are left alone
+        let img = resize_fn(striped_img(4, 1), (2, 1), &resize).unwrap();
+        assert_eq!(columns(&img, 2), vec![10, 20]);
+    }
+}